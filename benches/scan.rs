@@ -0,0 +1,83 @@
+//! End-to-end and per-file benchmarks over synthetic WAV trees.
+//!
+//! Run with `cargo bench`; criterion keeps the previous results under
+//! `target/criterion` and reports regressions against them.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+use tempfile::TempDir;
+use wav_files_stats::test_support::synthetic_tree;
+use wav_files_stats::{ScanOptions, calculate_duration, scan_directory};
+
+fn bench_scan_directory(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan_directory");
+    for file_count in [100, 1000] {
+        let dir = TempDir::new().unwrap();
+        synthetic_tree(dir.path(), file_count).unwrap();
+
+        group.throughput(Throughput::Elements(file_count as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(file_count),
+            dir.path(),
+            |b, root| b.iter(|| scan_directory(black_box(root), &ScanOptions::default())),
+        );
+    }
+    group.finish();
+}
+
+/// The same 1000-file scan with `batch_size`, to weigh the cost of the
+/// barrier after every batch against the bounded number of open files.
+fn bench_scan_directory_batched(c: &mut Criterion) {
+    let dir = TempDir::new().unwrap();
+    synthetic_tree(dir.path(), 1000).unwrap();
+
+    let mut group = c.benchmark_group("scan_directory_batched");
+    group.throughput(Throughput::Elements(1000));
+    for batch_size in [16, 256] {
+        let options = ScanOptions {
+            batch_size: Some(batch_size),
+            ..Default::default()
+        };
+        group.bench_with_input(
+            BenchmarkId::from_parameter(batch_size),
+            dir.path(),
+            |b, root| b.iter(|| scan_directory(black_box(root), &options)),
+        );
+    }
+    group.finish();
+}
+
+/// A 1000-file scan where half the files are not WAVs, to measure the
+/// error path: each failure is kept unformatted until it is printed.
+fn bench_scan_directory_with_errors(c: &mut Criterion) {
+    let dir = TempDir::new().unwrap();
+    synthetic_tree(dir.path(), 500).unwrap();
+    for i in 0..500 {
+        std::fs::write(dir.path().join(format!("corrupt_{i:05}.wav")), b"not a wav").unwrap();
+    }
+
+    let mut group = c.benchmark_group("scan_directory_with_errors");
+    group.throughput(Throughput::Elements(1000));
+    group.bench_function("half_corrupt", |b| {
+        b.iter(|| scan_directory(black_box(dir.path()), &ScanOptions::default()))
+    });
+    group.finish();
+}
+
+fn bench_calculate_duration(c: &mut Criterion) {
+    let dir = TempDir::new().unwrap();
+    let path = synthetic_tree(dir.path(), 1).unwrap().remove(0);
+
+    c.bench_function("calculate_duration", |b| {
+        b.iter(|| calculate_duration(black_box(&path)).unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_scan_directory,
+    bench_scan_directory_batched,
+    bench_scan_directory_with_errors,
+    bench_calculate_duration
+);
+criterion_main!(benches);
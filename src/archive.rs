@@ -0,0 +1,277 @@
+//! Reads WAV entries out of `.tar`, `.tar.gz` and `.tgz` archives, and
+//! single gzipped `.wav.gz` files.
+//!
+//! Tar is a sequential format, so the entries of one archive are read one
+//! after another on a single thread; only separate archives are processed in
+//! parallel. Each entry is identified as `archive.tar!/path/in/archive.wav`.
+
+use crate::scan::has_wav_extension;
+use crate::{Result, WavInfo, WavStatsError, duration_from_reader};
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// How much of an entry is buffered for header parsing. Only the header is
+/// needed, and entries can't be rewound, so a prefix is read into memory.
+const HEADER_PREFIX_LIMIT: u64 = 1 << 20;
+
+/// Whether `path` is a regular file named like a tar or gzipped tar archive.
+pub fn is_archive(path: &Path) -> bool {
+    let Some(name) = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_ascii_lowercase())
+    else {
+        return false;
+    };
+    path.is_file()
+        && [".tar", ".tar.gz", ".tgz"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+}
+
+/// Measures a gzipped WAV file from the decompressed start of its stream.
+/// The file size is the compressed size on disk. If the decompressed data
+/// is not a WAV, the error names the inner file: the name stored in the
+/// gzip header, or the path without `.gz`.
+pub fn gzipped_duration(path: &Path) -> Result<WavInfo> {
+    let file = File::open(path)?;
+    let metadata = file.metadata().ok();
+    let mut decoder = GzDecoder::new(BufReader::new(file));
+    let mut prefix = Vec::new();
+    (&mut decoder)
+        .take(HEADER_PREFIX_LIMIT)
+        .read_to_end(&mut prefix)
+        .map_err(|e| WavStatsError::Corrupt(format!("not valid gzip data: {}", e)))?;
+    let inner = decoder
+        .header()
+        .and_then(|header| header.filename())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .unwrap_or_else(|| {
+            path.with_extension("")
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+    let info = duration_from_reader(
+        path,
+        Cursor::new(prefix),
+        metadata.as_ref().map(|metadata| metadata.len()),
+    )
+    .map_err(|e| match e {
+        // Kept as is so the file is still counted as sub-frame.
+        WavStatsError::SubFrame(_) => e,
+        e => WavStatsError::Corrupt(format!("gzipped {} is not a valid WAV: {}", inner, e)),
+    })?;
+    Ok(WavInfo {
+        modified: metadata.and_then(|metadata| metadata.modified().ok()),
+        ..info
+    })
+}
+
+/// The path reported for `entry` inside `archive`.
+pub fn entry_path(archive: &Path, entry: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_os_string();
+    path.push("!/");
+    path.push(entry);
+    PathBuf::from(path)
+}
+
+/// Whether `path` looks like one made by [`entry_path`]: a component ends
+/// in `!` and nothing exists at the path itself.
+pub fn is_entry_path(path: &Path) -> bool {
+    !path.exists()
+        && path
+            .ancestors()
+            .filter_map(Path::file_name)
+            .any(|name| name.as_encoded_bytes().ends_with(b"!"))
+}
+
+fn open(path: &Path) -> Result<tar::Archive<Box<dyn Read>>> {
+    let file = BufReader::new(File::open(path)?);
+    let name = path.to_string_lossy().to_ascii_lowercase();
+    let reader: Box<dyn Read> = if name.ends_with(".gz") || name.ends_with(".tgz") {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+/// Reads every `.wav` entry of the archive at `path` for which `select`
+/// returns true, passing each one's reported path and result to `visit`.
+/// Stops early once `visit` returns false.
+///
+/// An error is returned if the archive itself can't be read, e.g. when it
+/// is truncated; entries visited up to that point keep their results.
+pub fn scan_archive(
+    path: &Path,
+    select: impl Fn(&Path) -> bool,
+    mut visit: impl FnMut(PathBuf, Result<WavInfo>) -> bool,
+) -> Result<()> {
+    let mut archive = open(path)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let reported = entry_path(path, &entry.path()?);
+        if !has_wav_extension(&reported) || !select(&reported) {
+            continue;
+        }
+
+        let size = entry.size();
+        let modified = entry
+            .header()
+            .mtime()
+            .ok()
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        let mut prefix = Vec::new();
+        let result = (&mut entry)
+            .take(HEADER_PREFIX_LIMIT)
+            .read_to_end(&mut prefix)
+            .map_err(WavStatsError::from)
+            .and_then(|_| duration_from_reader(&reported, Cursor::new(prefix), Some(size)))
+            .map(|info| WavInfo { modified, ..info });
+        if !visit(reported, result) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::WAVE_FORMAT_PCM;
+    use crate::header::tests::build_header;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn wav_bytes(secs: u32) -> Vec<u8> {
+        let mut bytes = build_header(WAVE_FORMAT_PCM, 1, 8000, 16, None, secs * 16000);
+        bytes.extend(std::iter::repeat_n(0u8, (secs * 16000) as usize));
+        bytes
+    }
+
+    fn build_tar(entries: &[(&str, Vec<u8>)]) -> anyhow::Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, &data[..])?;
+        }
+        Ok(builder.into_inner()?)
+    }
+
+    /// Each visited entry's reported path and duration (or error message).
+    type Visited = Vec<(PathBuf, Result<Duration, String>)>;
+
+    fn collect(path: &Path) -> (Visited, Result<()>) {
+        let mut seen = Vec::new();
+        let outcome = scan_archive(
+            path,
+            |_| true,
+            |reported, result| {
+                seen.push((
+                    reported,
+                    result.map(|info| info.duration).map_err(|e| e.to_string()),
+                ));
+                true
+            },
+        );
+        (seen, outcome)
+    }
+
+    fn gzip(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut encoder, bytes)?;
+        Ok(encoder.finish()?)
+    }
+
+    #[test]
+    fn test_gzipped_duration() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let good = dir.path().join("take.WAV.gz");
+        let compressed = gzip(&wav_bytes(2))?;
+        std::fs::write(&good, &compressed)?;
+        let info = gzipped_duration(&good)?;
+        assert_eq!(info.duration, Duration::from_secs(2));
+        assert_eq!(info.file_size, Some(compressed.len() as u64));
+
+        let bad = dir.path().join("bad.wav.gz");
+        std::fs::write(&bad, gzip(b"junk")?)?;
+        let message = gzipped_duration(&bad).unwrap_err().to_string();
+        assert!(
+            message.starts_with("gzipped bad.wav is not a valid WAV"),
+            "{}",
+            message
+        );
+
+        let plain = dir.path().join("plain.wav.gz");
+        std::fs::write(&plain, wav_bytes(1))?;
+        let message = gzipped_duration(&plain).unwrap_err().to_string();
+        assert!(message.starts_with("not valid gzip data"), "{}", message);
+
+        let result = crate::scan_directory(dir.path(), &crate::ScanOptions::default());
+        let summary = crate::Summary::from_scan(&result);
+        assert_eq!(summary.gzipped_files, 1);
+        assert_eq!(summary.error_count, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_archive_tar_and_tar_gz() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let tar_bytes = build_tar(&[
+            ("set/a.wav", wav_bytes(2)),
+            ("set/notes.txt", b"hello".to_vec()),
+            ("set/bad.WAV", b"junk".to_vec()),
+        ])?;
+        let tar_path = dir.path().join("data.tar");
+        std::fs::write(&tar_path, &tar_bytes)?;
+
+        let gz_path = dir.path().join("data.tar.gz");
+        std::fs::write(&gz_path, gzip(&tar_bytes)?)?;
+
+        assert_eq!(
+            entry_path(&tar_path, Path::new("set/a.wav")),
+            dir.path().join("data.tar!/set/a.wav")
+        );
+        assert!(is_entry_path(&entry_path(
+            &tar_path,
+            Path::new("set/a.wav")
+        )));
+        assert!(!is_entry_path(&tar_path));
+        for path in [&tar_path, &gz_path] {
+            assert!(is_archive(path));
+            let (seen, outcome) = collect(path);
+            outcome?;
+            assert_eq!(seen.len(), 2);
+            assert_eq!(seen[0].0, entry_path(path, Path::new("set/a.wav")));
+            assert_eq!(seen[0].1, Ok(Duration::from_secs(2)));
+            assert!(seen[1].1.is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_archive_truncated() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let tar_bytes = build_tar(&[("a.wav", wav_bytes(1)), ("b.wav", wav_bytes(1))])?;
+        let path = dir.path().join("cut.tar");
+        // Cut into the payload of the first entry, losing the second one.
+        std::fs::write(&path, &tar_bytes[..tar_bytes.len() - 20000])?;
+
+        let (seen, outcome) = collect(&path);
+        // The first header is intact, so that entry is still measured.
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].1, Ok(Duration::from_secs(1)));
+        assert!(outcome.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,517 @@
+//! Persistent per-file cache so unchanged files are not re-read between runs.
+//!
+//! Entries are keyed by path and invalidated when the file's size or
+//! modification time changes. Files whose path is not valid UTF-8 are never
+//! cached, since the cache is stored as JSON with string keys.
+//!
+//! [`tree_hash`] is the coarser variant for whole runs: one key for the
+//! path, size, and modification time of every input file.
+
+use crate::scan::{PathFilter, is_wav_file, measure_file};
+use crate::{DurationSource, WavInfo, archive};
+use anyhow::Context;
+use hound::{SampleFormat, WavSpec};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+const CACHE_VERSION: u32 = 1;
+
+/// How often `--resume` writes the cache to disk while scanning.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Size and modification time used to decide whether a cached entry is stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(FileStamp {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub stamp: FileStamp,
+    pub duration_nanos: u64,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    pub float: bool,
+    #[serde(default)]
+    pub source: DurationSource,
+    #[serde(default)]
+    pub data_size: Option<u64>,
+}
+
+impl CacheEntry {
+    fn new(stamp: FileStamp, info: &WavInfo) -> Self {
+        CacheEntry {
+            stamp,
+            duration_nanos: info.duration.as_nanos() as u64,
+            channels: info.spec.channels,
+            sample_rate: info.spec.sample_rate,
+            bits_per_sample: info.spec.bits_per_sample,
+            float: info.spec.sample_format == SampleFormat::Float,
+            source: info.source,
+            data_size: info.data_size,
+        }
+    }
+
+    fn to_info(self, path: &Path) -> WavInfo {
+        WavInfo {
+            path: path.to_path_buf(),
+            duration: Duration::from_nanos(self.duration_nanos),
+            spec: WavSpec {
+                channels: self.channels,
+                sample_rate: self.sample_rate,
+                bits_per_sample: self.bits_per_sample,
+                sample_format: if self.float {
+                    SampleFormat::Float
+                } else {
+                    SampleFormat::Int
+                },
+            },
+            source: self.source,
+            file_size: Some(self.stamp.size),
+            data_size: self.data_size,
+            modified: Some(
+                UNIX_EPOCH + Duration::new(self.stamp.modified_secs, self.stamp.modified_nanos),
+            ),
+        }
+    }
+}
+
+/// The on-disk cache format.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CacheFile {
+    pub version: u32,
+    pub entries: BTreeMap<String, CacheEntry>,
+}
+
+impl CacheFile {
+    /// Loads a cache file, returning an empty cache if it does not exist yet
+    /// or was written by an incompatible version.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(CacheFile::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read cache {}", path.display()));
+            }
+        };
+        let cache: CacheFile = serde_json::from_slice(&data)
+            .with_context(|| format!("Failed to parse cache {}", path.display()))?;
+        if cache.version != CACHE_VERSION {
+            return Ok(CacheFile::default());
+        }
+        Ok(cache)
+    }
+
+    /// Writes the cache to a temporary file next to `path` and renames it
+    /// into place, so a crash mid-write never leaves a truncated cache.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut tmp_name = path.as_os_str().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_name);
+
+        let mut file = fs::File::create(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        serde_json::to_writer(&mut file, self)?;
+        file.flush()?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace cache {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Cache state shared by the scanning threads.
+pub struct ScanCache {
+    path: PathBuf,
+    previous: BTreeMap<String, CacheEntry>,
+    seen: Mutex<BTreeMap<String, CacheEntry>>,
+    /// Time of the last flush; `None` unless `--resume` is active.
+    last_flush: Option<Mutex<Instant>>,
+}
+
+impl ScanCache {
+    pub fn open(path: &Path, resume: bool) -> anyhow::Result<Self> {
+        Ok(ScanCache {
+            path: path.to_path_buf(),
+            previous: CacheFile::load(path)?.entries,
+            seen: Mutex::new(BTreeMap::new()),
+            last_flush: resume.then(|| Mutex::new(Instant::now())),
+        })
+    }
+
+    /// Returns the cached info for `path` if its stamp still matches.
+    pub fn lookup(&self, path: &Path, stamp: FileStamp) -> Option<WavInfo> {
+        let key = path.to_str()?;
+        let entry = self
+            .previous
+            .get(key)
+            .filter(|entry| entry.stamp == stamp)?;
+        self.seen.lock().unwrap().insert(key.to_string(), *entry);
+        Some(entry.to_info(path))
+    }
+
+    /// Records a freshly computed result, flushing to disk if `--resume` is
+    /// active and the flush interval has elapsed.
+    pub fn record(&self, path: &Path, stamp: FileStamp, info: &WavInfo) -> anyhow::Result<()> {
+        let Some(key) = path.to_str() else {
+            return Ok(());
+        };
+        self.seen
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), CacheEntry::new(stamp, info));
+
+        // Only one thread flushes at a time; the others skip rather than wait.
+        if let Some(Ok(mut last_flush)) = self.last_flush.as_ref().map(Mutex::try_lock)
+            && last_flush.elapsed() >= FLUSH_INTERVAL
+        {
+            self.snapshot(true).save(&self.path)?;
+            *last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Builds the cache file contents. Mid-scan snapshots keep the entries of
+    /// files not visited yet so an interrupted run loses nothing; the final
+    /// snapshot only keeps files seen in this run, dropping deleted ones.
+    fn snapshot(&self, keep_unvisited: bool) -> CacheFile {
+        let seen = self.seen.lock().unwrap();
+        let mut entries = if keep_unvisited {
+            self.previous.clone()
+        } else {
+            BTreeMap::new()
+        };
+        entries.extend(seen.iter().map(|(key, entry)| (key.clone(), *entry)));
+        CacheFile {
+            version: CACHE_VERSION,
+            entries,
+        }
+    }
+
+    /// Takes over the entries of files this run did not visit, for
+    /// `--accumulate`, so the cache keeps the whole history and not just the
+    /// last scanned tree. Each entry is checked against the file on disk:
+    /// unchanged files are kept without being read, changed ones are read
+    /// again, and files that no longer exist or no longer read are dropped.
+    pub fn carry_over(&self) -> CarryOver {
+        let unvisited: Vec<(&String, &CacheEntry)> = {
+            let seen = self.seen.lock().unwrap();
+            self.previous
+                .iter()
+                .filter(|(key, _)| !seen.contains_key(*key))
+                .collect()
+        };
+        // A dropped entry is `Err(true)` if its file is gone.
+        let outcomes: Vec<Result<(String, CacheEntry, WavInfo), bool>> = unvisited
+            .into_par_iter()
+            .map(|(key, entry)| {
+                let path = Path::new(key);
+                let stamp =
+                    FileStamp::of(path).ok_or_else(|| matches!(path.try_exists(), Ok(false)))?;
+                if stamp == entry.stamp {
+                    return Ok((key.clone(), *entry, entry.to_info(path)));
+                }
+                let info = measure_file(path).map_err(|_| false)?;
+                Ok((key.clone(), CacheEntry::new(stamp, &info), info))
+            })
+            .collect();
+
+        let mut carried = CarryOver::default();
+        let mut seen = self.seen.lock().unwrap();
+        for outcome in outcomes {
+            match outcome {
+                Ok((key, entry, info)) => {
+                    seen.insert(key, entry);
+                    carried.files.push(info);
+                }
+                Err(true) => carried.removed += 1,
+                Err(false) => carried.unreadable += 1,
+            }
+        }
+        carried
+    }
+
+    /// Writes the final cache after a completed scan.
+    pub fn finish(&self) -> anyhow::Result<()> {
+        self.snapshot(false).save(&self.path)
+    }
+}
+
+/// What [`ScanCache::carry_over`] kept of the files a run didn't visit.
+#[derive(Debug, Default)]
+pub struct CarryOver {
+    pub files: Vec<WavInfo>,
+    /// Entries dropped because their file no longer exists.
+    pub removed: usize,
+    /// Entries dropped because their file changed and no longer reads, or
+    /// can't be checked.
+    pub unreadable: usize,
+}
+
+/// Hashes the relative path and [`FileStamp`] of every `.wav` file (and
+/// archive) under `root` that passes `filter`, after `salt`, into one hex
+/// SHA-256 key. The key changes whenever a file is added, removed, renamed,
+/// resized, or touched, or the salt changes; nothing is read but metadata.
+pub fn tree_hash(
+    root: &Path,
+    filter: Option<&PathFilter>,
+    follow_symlinks: bool,
+    salt: &[u8],
+) -> String {
+    let mut inputs: Vec<(PathBuf, Option<FileStamp>)> = walkdir::WalkDir::new(root)
+        .follow_links(follow_symlinks)
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Ok(entry) => {
+                let path = entry.path();
+                let relative = path.strip_prefix(root).unwrap_or(path);
+                let selected = filter.is_none_or(|filter| filter.matches(relative));
+                ((is_wav_file(path) || archive::is_archive(path)) && selected)
+                    .then(|| (relative.to_path_buf(), FileStamp::of(path)))
+            }
+            // An unreadable directory is part of the tree's state too.
+            Err(e) => Some((PathBuf::from(e.to_string()), None)),
+        })
+        .collect();
+    inputs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut hasher = Sha256::new();
+    hasher.update((salt.len() as u64).to_le_bytes());
+    hasher.update(salt);
+    for (path, stamp) in inputs {
+        let path = path.as_os_str().as_encoded_bytes();
+        hasher.update((path.len() as u64).to_le_bytes());
+        hasher.update(path);
+        match stamp {
+            Some(stamp) => {
+                hasher.update([1]);
+                hasher.update(stamp.size.to_le_bytes());
+                hasher.update(stamp.modified_secs.to_le_bytes());
+                hasher.update(stamp.modified_nanos.to_le_bytes());
+            }
+            None => hasher.update([0]),
+        }
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn info(secs: u64) -> WavInfo {
+        WavInfo {
+            path: PathBuf::from("/data/a.wav"),
+            duration: Duration::from_secs(secs),
+            spec: WavSpec {
+                channels: 2,
+                sample_rate: 48000,
+                bits_per_sample: 24,
+                sample_format: SampleFormat::Int,
+            },
+            source: DurationSource::FactChunk,
+            file_size: None,
+            data_size: Some(1000),
+            modified: None,
+        }
+    }
+
+    fn stamp(size: u64) -> FileStamp {
+        FileStamp {
+            size,
+            modified_secs: 1_700_000_000,
+            modified_nanos: 5,
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let cache_path = dir.path().join("cache.json");
+
+        let cache = ScanCache::open(&cache_path, false)?;
+        cache.record(Path::new("/data/a.wav"), stamp(10), &info(7))?;
+        cache.finish()?;
+
+        // No temporary file is left behind after the rename.
+        assert!(!dir.path().join("cache.json.tmp").exists());
+
+        let reopened = ScanCache::open(&cache_path, false)?;
+        let hit = reopened
+            .lookup(Path::new("/data/a.wav"), stamp(10))
+            .unwrap();
+        assert_eq!(hit.duration, Duration::from_secs(7));
+        assert_eq!(hit.spec, info(7).spec);
+        assert_eq!(hit.source, DurationSource::FactChunk);
+        assert_eq!(hit.file_size, Some(10));
+        assert_eq!(hit.data_size, Some(1000));
+
+        // A changed size invalidates the entry.
+        assert!(
+            reopened
+                .lookup(Path::new("/data/a.wav"), stamp(11))
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_final_snapshot_drops_unvisited_entries() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let cache_path = dir.path().join("cache.json");
+
+        let cache = ScanCache::open(&cache_path, false)?;
+        cache.record(Path::new("/data/a.wav"), stamp(1), &info(1))?;
+        cache.record(Path::new("/data/b.wav"), stamp(2), &info(2))?;
+        cache.finish()?;
+
+        let cache = ScanCache::open(&cache_path, true)?;
+        assert!(cache.lookup(Path::new("/data/a.wav"), stamp(1)).is_some());
+
+        // Mid-scan snapshots keep b.wav so an interrupted run can resume ...
+        assert_eq!(cache.snapshot(true).entries.len(), 2);
+        // ... but a completed scan that never saw b.wav forgets it.
+        cache.finish()?;
+        assert_eq!(CacheFile::load(&cache_path)?.entries.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_carry_over_rereads_changed_gzipped_wav() -> anyhow::Result<()> {
+        use crate::test_support::{pcm16_spec, write_wav};
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+
+        let dir = TempDir::new()?;
+        let cache_path = dir.path().join("cache.json");
+        let plain = dir.path().join("take.wav");
+        let gzipped = dir.path().join("take.wav.gz");
+        std::fs::write(&gzipped, b"not read while unchanged")?;
+        let cache = ScanCache::open(&cache_path, false)?;
+        cache.record(&gzipped, FileStamp::of(&gzipped).unwrap(), &info(9))?;
+        cache.finish()?;
+
+        // Replaced by one second of gzipped audio.
+        write_wav(&plain, pcm16_spec(1, 8000), 8000)?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&std::fs::read(&plain)?)?;
+        std::fs::write(&gzipped, encoder.finish()?)?;
+
+        let cache = ScanCache::open(&cache_path, false)?;
+        let carried = cache.carry_over();
+        assert_eq!((carried.removed, carried.unreadable), (0, 0));
+        assert_eq!(carried.files.len(), 1);
+        assert_eq!(carried.files[0].duration, Duration::from_secs(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_carry_over_keeps_history_and_drops_deleted() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let cache_path = dir.path().join("cache.json");
+        let old = dir.path().join("old.wav");
+        let gone = dir.path().join("gone.wav");
+        let broken = dir.path().join("broken.wav");
+        let new = dir.path().join("new.wav");
+        for path in [&old, &gone, &broken, &new] {
+            std::fs::write(path, b"not read while unchanged")?;
+        }
+
+        let cache = ScanCache::open(&cache_path, false)?;
+        cache.record(&old, FileStamp::of(&old).unwrap(), &info(3))?;
+        cache.record(&gone, FileStamp::of(&gone).unwrap(), &info(4))?;
+        cache.record(&broken, FileStamp::of(&broken).unwrap(), &info(6))?;
+        cache.finish()?;
+        std::fs::remove_file(&gone)?;
+        // Changed, so it is read again, and it isn't a WAV file.
+        std::fs::write(&broken, b"overwritten")?;
+
+        // The next run only scans the new file.
+        let cache = ScanCache::open(&cache_path, false)?;
+        cache.record(&new, FileStamp::of(&new).unwrap(), &info(5))?;
+        let CarryOver {
+            files,
+            removed,
+            unreadable,
+        } = cache.carry_over();
+        assert_eq!((removed, unreadable), (1, 1));
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, old);
+        assert_eq!(files[0].duration, Duration::from_secs(3));
+
+        cache.finish()?;
+        let keys: Vec<String> = CacheFile::load(&cache_path)?.entries.into_keys().collect();
+        assert_eq!(keys, vec![new.to_str().unwrap(), old.to_str().unwrap()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_missing_cache_is_empty() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let cache = CacheFile::load(&dir.path().join("missing.json"))?;
+        assert!(cache.entries.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_hash() -> anyhow::Result<()> {
+        use crate::test_support::{pcm16_spec, write_wav};
+
+        let dir = TempDir::new()?;
+        write_wav(&dir.path().join("a.wav"), pcm16_spec(1, 8000), 800)?;
+        fs::create_dir(dir.path().join("sub"))?;
+        write_wav(&dir.path().join("sub/b.wav"), pcm16_spec(1, 8000), 800)?;
+        let hash =
+            |filter: Option<&PathFilter>, salt: &[u8]| tree_hash(dir.path(), filter, false, salt);
+        let key = hash(None, b"");
+        assert_eq!(key.len(), 64);
+        assert_eq!(hash(None, b""), key);
+        assert_ne!(hash(None, b"--histogram"), key);
+
+        // Files that are never read don't count.
+        fs::write(dir.path().join("notes.txt"), "unrelated")?;
+        assert_eq!(hash(None, b""), key);
+        let only_a = PathFilter::new(&[], &["sub/*".to_string()])?;
+        let filtered = hash(Some(&only_a), b"");
+        assert_ne!(filtered, key);
+
+        // Touching a file the filter leaves out changes only the full key.
+        fs::File::options()
+            .write(true)
+            .open(dir.path().join("sub/b.wav"))?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_700_000_000))?;
+        assert_ne!(hash(None, b""), key);
+        assert_eq!(hash(Some(&only_a), b""), filtered);
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+//! The error type of the functions that read WAV files, so callers can
+//! match on why a file failed instead of parsing a message.
+
+use crate::header::WavHeader;
+use crate::{SubFrameError, WavInfo};
+use std::fmt;
+use std::io;
+
+/// Why a WAV file, or a directory entry on the way to one, could not be
+/// read.
+#[derive(Debug)]
+pub enum WavStatsError {
+    /// The file could not be opened or read.
+    Io(io::Error),
+    /// The directory walk failed, e.g. on an unreadable directory.
+    Walk(walkdir::Error),
+    /// hound rejected the file, e.g. for a missing RIFF tag.
+    Format(hound::Error),
+    /// The file declares no audio at all.
+    Empty(FileDetails),
+    /// The header declares zero channels or a sample rate of zero.
+    ZeroSpec { channels: u16, sample_rate: u32 },
+    /// The audio is not empty, but too short for one complete frame.
+    SubFrame(SubFrameError),
+    /// The header is malformed or inconsistent in a way our own parser
+    /// detects; the message says how.
+    Corrupt(String),
+}
+
+/// What could still be read of a file that is empty, sub-frame, or has a
+/// suspicious spec, so its warning can be triaged without reopening it. Filled in from
+/// what was parsed while measuring the file.
+/// Every field is `None` when that part of the file couldn't be read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileDetails {
+    /// Size on disk.
+    pub file_size: Option<u64>,
+    pub channels: Option<u16>,
+    pub sample_rate: Option<u32>,
+    pub bits_per_sample: Option<u16>,
+}
+
+impl FileDetails {
+    /// What `header`, if it parsed, and the file size say.
+    pub(crate) fn of_header(header: Option<&WavHeader>, file_size: Option<u64>) -> Self {
+        FileDetails {
+            file_size,
+            channels: header.map(|header| header.channels),
+            sample_rate: header.map(|header| header.sample_rate),
+            bits_per_sample: header.map(|header| header.bits_per_sample),
+        }
+    }
+
+    pub(crate) fn of(info: &WavInfo) -> Self {
+        FileDetails {
+            file_size: info.file_size,
+            channels: Some(info.spec.channels),
+            sample_rate: Some(info.spec.sample_rate),
+            bits_per_sample: Some(info.spec.bits_per_sample),
+        }
+    }
+}
+
+/// The known fields as " (44 bytes, 2 ch, 44100 Hz, 16-bit)", or nothing if
+/// none are known.
+impl fmt::Display for FileDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = [
+            self.file_size.map(|size| format!("{} bytes", size)),
+            self.channels.map(|channels| format!("{} ch", channels)),
+            self.sample_rate.map(|rate| format!("{} Hz", rate)),
+            self.bits_per_sample.map(|bits| format!("{}-bit", bits)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if parts.is_empty() {
+            return Ok(());
+        }
+        write!(f, " ({})", parts.join(", "))
+    }
+}
+
+/// A `Result` whose error defaults to [`WavStatsError`].
+pub type Result<T, E = WavStatsError> = std::result::Result<T, E>;
+
+impl fmt::Display for WavStatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WavStatsError::Io(error) => write!(f, "{}", error),
+            WavStatsError::Walk(error) => write!(f, "{}", error),
+            WavStatsError::Format(error) => write!(f, "{}", error),
+            WavStatsError::Empty(_) => write!(f, "Empty audio file"),
+            WavStatsError::ZeroSpec { channels: 0, .. } => {
+                write!(f, "Invalid format: header declares zero channels")
+            }
+            WavStatsError::ZeroSpec { .. } => {
+                write!(f, "Invalid format: header declares a sample rate of zero")
+            }
+            WavStatsError::SubFrame(error) => write!(f, "{}", error),
+            WavStatsError::Corrupt(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for WavStatsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WavStatsError::Io(error) => Some(error),
+            WavStatsError::Walk(error) => Some(error),
+            WavStatsError::Format(error) => Some(error),
+            WavStatsError::SubFrame(error) => Some(error),
+            WavStatsError::Empty(_)
+            | WavStatsError::ZeroSpec { .. }
+            | WavStatsError::Corrupt(_) => None,
+        }
+    }
+}
+
+impl WavStatsError {
+    /// Attaches `details` to an empty or sub-frame error; others are
+    /// returned unchanged.
+    pub(crate) fn with_details(self, details: FileDetails) -> Self {
+        match self {
+            WavStatsError::Empty(_) => WavStatsError::Empty(details),
+            WavStatsError::SubFrame(error) => {
+                WavStatsError::SubFrame(SubFrameError { details, ..error })
+            }
+            error => error,
+        }
+    }
+}
+
+impl From<io::Error> for WavStatsError {
+    fn from(error: io::Error) -> Self {
+        WavStatsError::Io(error)
+    }
+}
+
+impl From<walkdir::Error> for WavStatsError {
+    fn from(error: walkdir::Error) -> Self {
+        WavStatsError::Walk(error)
+    }
+}
+
+/// hound wraps the I/O errors it hits, which are unwrapped again so they
+/// land in [`WavStatsError::Io`] whichever reader saw them.
+impl From<hound::Error> for WavStatsError {
+    fn from(error: hound::Error) -> Self {
+        match error {
+            hound::Error::IoError(error) => WavStatsError::Io(error),
+            error => WavStatsError::Format(error),
+        }
+    }
+}
+
+impl From<SubFrameError> for WavStatsError {
+    fn from(error: SubFrameError) -> Self {
+        WavStatsError::SubFrame(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kinds_and_messages() {
+        let error = WavStatsError::from(hound::Error::FormatError("no RIFF tag found"));
+        assert!(matches!(error, WavStatsError::Format(_)));
+        assert_eq!(error.to_string(), "Ill-formed WAVE file: no RIFF tag found");
+
+        let error = WavStatsError::from(hound::Error::IoError(io::ErrorKind::UnexpectedEof.into()));
+        assert!(matches!(&error, WavStatsError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+
+        let zero_channels = WavStatsError::ZeroSpec {
+            channels: 0,
+            sample_rate: 8000,
+        };
+        assert_eq!(
+            zero_channels.to_string(),
+            "Invalid format: header declares zero channels"
+        );
+        let zero_rate = WavStatsError::ZeroSpec {
+            channels: 2,
+            sample_rate: 0,
+        };
+        assert_eq!(
+            zero_rate.to_string(),
+            "Invalid format: header declares a sample rate of zero"
+        );
+        assert_eq!(
+            WavStatsError::Empty(FileDetails::default()).to_string(),
+            "Empty audio file"
+        );
+    }
+}
@@ -0,0 +1,460 @@
+//! Minimal RIFF/WAVE header parser for the fields hound does not expose.
+//!
+//! Only the chunk headers and the small `fmt ` and `fact` chunks are read;
+//! parsing stops at the start of the `data` chunk, so the audio payload is
+//! never touched.
+//!
+//! RF64 and BW64 files (the 64-bit variants used for recordings over 4 GiB)
+//! are recognized too: their `ds64` chunk supplies the sizes that no longer
+//! fit the 32-bit chunk headers. So are RIFX files, the big-endian RIFF
+//! variant, whose sizes and `fmt ` fields are stored most significant byte
+//! first.
+
+use crate::{Result, WavStatsError};
+use std::io::{self, Read};
+
+pub const WAVE_FORMAT_PCM: u16 = 0x0001;
+pub const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+pub const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Header fields of a WAV file, up to the start of its `data` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavHeader {
+    /// The codec; for `WAVE_FORMAT_EXTENSIBLE` this is the sub-format's tag.
+    pub format_tag: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub byte_rate: u32,
+    pub block_align: u16,
+    pub bits_per_sample: u16,
+    /// Samples per channel from the `fact` chunk, if there is one.
+    pub fact_samples: Option<u32>,
+    /// Size of the `data` chunk in bytes.
+    pub data_size: u64,
+    /// Offset of the audio payload from the start of the file, just past
+    /// the `data` chunk header.
+    pub data_offset: u64,
+    /// Whether the file is an RF64/BW64 container rather than plain RIFF.
+    pub rf64: bool,
+    /// Whether the file is big-endian RIFX rather than little-endian RIFF.
+    pub big_endian: bool,
+    /// Whether the `fmt ` chunk is a `WAVE_FORMAT_EXTENSIBLE` one.
+    pub extensible: bool,
+    /// Samples per channel from the `ds64` chunk of an RF64 file; zero or
+    /// absent when the writer left it unset.
+    pub ds64_samples: Option<u64>,
+}
+
+impl WavHeader {
+    /// Whether the codec is something other than plain PCM or IEEE float.
+    pub fn is_compressed(&self) -> bool {
+        !matches!(self.format_tag, WAVE_FORMAT_PCM | WAVE_FORMAT_IEEE_FLOAT)
+    }
+}
+
+struct Fmt {
+    format_tag: u16,
+    channels: u16,
+    sample_rate: u32,
+    byte_rate: u32,
+    block_align: u16,
+    bits_per_sample: u16,
+    extensible: bool,
+}
+
+fn read_array<const N: usize, R: Read>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn skip<R: Read>(reader: &mut R, len: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    if skipped < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Decodes a 32-bit field in the file's byte order.
+fn u32_from(bytes: [u8; 4], big_endian: bool) -> u32 {
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+fn parse_fmt(chunk: &[u8], big_endian: bool) -> Result<Fmt> {
+    if chunk.len() < 16 {
+        return Err(WavStatsError::Corrupt(format!(
+            "fmt chunk is too short ({} bytes)",
+            chunk.len()
+        )));
+    }
+    let u16_at = |i: usize| {
+        let bytes = [chunk[i], chunk[i + 1]];
+        if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    };
+    let u32_at = |i: usize| {
+        u32_from(
+            [chunk[i], chunk[i + 1], chunk[i + 2], chunk[i + 3]],
+            big_endian,
+        )
+    };
+
+    let mut format_tag = u16_at(0);
+    let extensible = format_tag == WAVE_FORMAT_EXTENSIBLE;
+    // WAVEFORMATEXTENSIBLE stores the real codec in the first two bytes of
+    // the sub-format GUID, after cbSize, valid bits, and the channel mask.
+    if extensible && chunk.len() >= 26 {
+        format_tag = u16_at(24);
+    }
+
+    Ok(Fmt {
+        format_tag,
+        channels: u16_at(2),
+        sample_rate: u32_at(4),
+        byte_rate: u32_at(8),
+        block_align: u16_at(12),
+        bits_per_sample: u16_at(14),
+        extensible,
+    })
+}
+
+/// What [`detect_misnamed`] reports for bytes that match no known format.
+pub const UNRECOGNIZED_FORMAT: &str = "unrecognized format";
+
+/// Identifies what a file named `.wav` really contains from its first bytes.
+/// Returns `None` if it starts like a WAV (RIFF, RIFX, RF64 or BW64) or is too
+/// short to tell, and otherwise the detected format's name.
+pub fn detect_misnamed(prefix: &[u8]) -> Option<&'static str> {
+    if prefix.len() < 4 {
+        return None;
+    }
+    let format_at_8 = prefix.get(8..12);
+    let detected = match &prefix[..4] {
+        b"RIFF" | b"RIFX" | b"RF64" | b"BW64"
+            if format_at_8.is_none_or(|format| format == b"WAVE") =>
+        {
+            return None;
+        }
+        b"RIFF" if format_at_8 == Some(b"AVI ") => "AVI",
+        b"RIFF" => "non-WAVE RIFF",
+        b"FORM" if matches!(format_at_8, Some(b"AIFF") | Some(b"AIFC")) => "AIFF",
+        b"fLaC" => "FLAC",
+        b"OggS" => "Ogg",
+        [b'I', b'D', b'3', _] => "MP3",
+        // An MPEG audio frame sync: 11 set bits.
+        [0xFF, second, _, _] if second & 0xE0 == 0xE0 => "MP3",
+        _ => UNRECOGNIZED_FORMAT,
+    };
+    Some(detected)
+}
+
+/// Sizes from an RF64 `ds64` chunk.
+struct Ds64 {
+    data_size: u64,
+    sample_count: u64,
+}
+
+/// A 32-bit chunk size meaning "see the `ds64` chunk" in RF64 files.
+const RF64_SIZE_PLACEHOLDER: u64 = 0xFFFF_FFFF;
+
+/// The largest `fmt ` chunk read. Real ones are at most 40 bytes, plus a
+/// few for codec-specific extras; the declared size is taken from the file,
+/// so it is checked before that much is allocated.
+const MAX_FMT_CHUNK: u64 = 64 * 1024;
+
+/// Reads the header of a WAV stream, stopping at the `data` chunk.
+pub fn read_header<R: Read>(mut reader: R) -> Result<WavHeader> {
+    let riff: [u8; 4] = read_array(&mut reader)?;
+    let _riff_size: [u8; 4] = read_array(&mut reader)?;
+    let wave: [u8; 4] = read_array(&mut reader)?;
+    let rf64 = matches!(&riff, b"RF64" | b"BW64");
+    let big_endian = &riff == b"RIFX";
+    if !(rf64 || big_endian || &riff == b"RIFF") || &wave != b"WAVE" {
+        return Err(WavStatsError::Corrupt("not a RIFF/WAVE file".to_string()));
+    }
+
+    let mut fmt = None;
+    let mut fact_samples = None;
+    let mut ds64 = None;
+    // Past the RIFF header, then each chunk header.
+    let mut offset = 12;
+    loop {
+        let id: [u8; 4] = read_array(&mut reader)?;
+        let len = u32_from(read_array(&mut reader)?, big_endian) as u64;
+        offset += 8;
+        match &id {
+            b"fmt " => {
+                if len > MAX_FMT_CHUNK {
+                    return Err(WavStatsError::Corrupt(format!(
+                        "fmt chunk declares {} bytes, more than the {} a format needs",
+                        len, MAX_FMT_CHUNK
+                    )));
+                }
+                let mut chunk = vec![0u8; len as usize];
+                reader.read_exact(&mut chunk)?;
+                fmt = Some(parse_fmt(&chunk, big_endian)?);
+            }
+            b"ds64" if rf64 && len >= 24 => {
+                let _riff_size: [u8; 8] = read_array(&mut reader)?;
+                ds64 = Some(Ds64 {
+                    data_size: u64::from_le_bytes(read_array(&mut reader)?),
+                    sample_count: u64::from_le_bytes(read_array(&mut reader)?),
+                });
+                // The rest is a table of other oversized chunks we don't need.
+                skip(&mut reader, len - 24)?;
+            }
+            b"fact" if len >= 4 => {
+                fact_samples = Some(u32_from(read_array(&mut reader)?, big_endian));
+                skip(&mut reader, len - 4)?;
+            }
+            b"data" => {
+                let Some(fmt) = fmt else {
+                    return Err(WavStatsError::Corrupt(
+                        "data chunk precedes the fmt chunk".to_string(),
+                    ));
+                };
+                let data_size = match (&ds64, rf64 && len == RF64_SIZE_PLACEHOLDER) {
+                    (Some(ds64), true) => ds64.data_size,
+                    (None, true) => {
+                        return Err(WavStatsError::Corrupt(
+                            "RF64 file has no ds64 chunk".to_string(),
+                        ));
+                    }
+                    (_, false) => len,
+                };
+                return Ok(WavHeader {
+                    format_tag: fmt.format_tag,
+                    channels: fmt.channels,
+                    sample_rate: fmt.sample_rate,
+                    byte_rate: fmt.byte_rate,
+                    block_align: fmt.block_align,
+                    bits_per_sample: fmt.bits_per_sample,
+                    fact_samples,
+                    data_size,
+                    data_offset: offset,
+                    rf64,
+                    big_endian,
+                    extensible: fmt.extensible,
+                    ds64_samples: ds64
+                        .map(|ds64| ds64.sample_count)
+                        .filter(|&samples| samples > 0),
+                });
+            }
+            _ => skip(&mut reader, len)?,
+        }
+        // Chunks are padded to an even number of bytes.
+        if len % 2 == 1 {
+            skip(&mut reader, 1)?;
+        }
+        offset += len + len % 2;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds a WAV header with the given fmt fields and optional fact chunk,
+    /// followed by an (empty) data chunk that declares `data_size` bytes.
+    pub(crate) fn build_header(
+        format_tag: u16,
+        channels: u16,
+        sample_rate: u32,
+        bits_per_sample: u16,
+        fact_samples: Option<u32>,
+        data_size: u32,
+    ) -> Vec<u8> {
+        let block_align = (channels * bits_per_sample).div_ceil(8);
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&format_tag.to_le_bytes());
+        fmt.extend_from_slice(&channels.to_le_bytes());
+        fmt.extend_from_slice(&sample_rate.to_le_bytes());
+        fmt.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        fmt.extend_from_slice(&block_align.to_le_bytes());
+        fmt.extend_from_slice(&bits_per_sample.to_le_bytes());
+
+        let mut body = b"WAVE".to_vec();
+        body.extend_from_slice(b"fmt ");
+        body.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+        body.extend_from_slice(&fmt);
+        if let Some(samples) = fact_samples {
+            body.extend_from_slice(b"fact");
+            body.extend_from_slice(&4u32.to_le_bytes());
+            body.extend_from_slice(&samples.to_le_bytes());
+        }
+        body.extend_from_slice(b"data");
+        body.extend_from_slice(&data_size.to_le_bytes());
+
+        let mut out = b"RIFF".to_vec();
+        out.extend_from_slice(&(body.len() as u32 + data_size).to_le_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+
+    #[test]
+    fn test_read_header_minimal_fixture() -> anyhow::Result<()> {
+        let header = read_header(&include_bytes!("../test_data/minimal_wav_header.bin")[..])?;
+        assert_eq!(header.format_tag, WAVE_FORMAT_PCM);
+        assert_eq!(header.channels, 1);
+        assert_eq!(header.sample_rate, 44100);
+        assert_eq!(header.byte_rate, 88200);
+        assert_eq!(header.block_align, 2);
+        assert_eq!(header.bits_per_sample, 16);
+        assert_eq!(header.fact_samples, None);
+        assert_eq!(header.data_size, 88200);
+        assert_eq!(header.data_offset, 44);
+        assert!(!header.is_compressed());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_header_with_fact_and_odd_chunk() -> anyhow::Result<()> {
+        let mut bytes = build_header(0x0002, 2, 22050, 4, Some(44100), 1000);
+        // Insert an odd-sized unknown chunk (plus pad byte) before fmt.
+        let extra = [b'L', b'I', b'S', b'T', 3, 0, 0, 0, 1, 2, 3, 0];
+        bytes.splice(12..12, extra);
+
+        let header = read_header(&bytes[..])?;
+        assert_eq!(header.format_tag, 0x0002);
+        assert!(header.is_compressed());
+        assert_eq!(header.fact_samples, Some(44100));
+        assert_eq!(header.data_size, 1000);
+        assert_eq!(header.data_offset, 68);
+        Ok(())
+    }
+
+    /// Rewrites a header from [`build_header`] as RF64: the 32-bit sizes
+    /// become placeholders and a `ds64` chunk carries the real ones.
+    pub(crate) fn to_rf64(mut bytes: Vec<u8>, data_size: u64, sample_count: u64) -> Vec<u8> {
+        bytes[0..4].copy_from_slice(b"RF64");
+        bytes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        let data_len_at = bytes.len() - 4;
+        bytes[data_len_at..].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let mut ds64 = b"ds64".to_vec();
+        ds64.extend_from_slice(&28u32.to_le_bytes());
+        ds64.extend_from_slice(&(bytes.len() as u64 + data_size).to_le_bytes());
+        ds64.extend_from_slice(&data_size.to_le_bytes());
+        ds64.extend_from_slice(&sample_count.to_le_bytes());
+        ds64.extend_from_slice(&0u32.to_le_bytes()); // empty size table
+        bytes.splice(12..12, ds64);
+        bytes
+    }
+
+    #[test]
+    fn test_read_header_rf64() -> anyhow::Result<()> {
+        let six_gib = 6 * 1024 * 1024 * 1024;
+        let bytes = to_rf64(
+            build_header(WAVE_FORMAT_PCM, 2, 48000, 24, None, 0),
+            six_gib,
+            0,
+        );
+
+        let header = read_header(&bytes[..])?;
+        assert!(header.rf64);
+        assert_eq!(header.data_size, six_gib);
+        assert_eq!(header.ds64_samples, None);
+        assert_eq!(header.channels, 2);
+        Ok(())
+    }
+
+    /// Rewrites a header from [`build_header`] as big-endian RIFX by
+    /// swapping the byte order of every size and `fmt ` field.
+    pub(crate) fn to_rifx(mut bytes: Vec<u8>) -> Vec<u8> {
+        bytes[0..4].copy_from_slice(b"RIFX");
+        bytes[4..8].reverse();
+        let mut at = 12;
+        while at + 8 <= bytes.len() {
+            let len = u32::from_le_bytes(bytes[at + 4..at + 8].try_into().unwrap()) as usize;
+            bytes[at + 4..at + 8].reverse();
+            let body = at + 8;
+            match &bytes[at..at + 4] {
+                b"fmt " => {
+                    for (field, width) in [(0, 2), (2, 2), (4, 4), (8, 4), (12, 2), (14, 2)] {
+                        bytes[body + field..body + field + width].reverse();
+                    }
+                }
+                b"fact" => bytes[body..body + 4].reverse(),
+                _ => {}
+            }
+            at = body + len;
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_read_header_rifx() -> anyhow::Result<()> {
+        let bytes = to_rifx(build_header(
+            WAVE_FORMAT_PCM,
+            2,
+            22050,
+            16,
+            Some(11025),
+            44100,
+        ));
+        assert_eq!(&bytes[4..8], &(36u32 + 12 + 44100).to_be_bytes());
+
+        let header = read_header(&bytes[..])?;
+        assert!(header.big_endian);
+        assert!(!header.rf64);
+        assert_eq!(header.format_tag, WAVE_FORMAT_PCM);
+        assert_eq!(header.channels, 2);
+        assert_eq!(header.sample_rate, 22050);
+        assert_eq!(header.byte_rate, 88200);
+        assert_eq!(header.block_align, 4);
+        assert_eq!(header.bits_per_sample, 16);
+        assert_eq!(header.fact_samples, Some(11025));
+        assert_eq!(header.data_size, 44100);
+        assert_eq!(header.data_offset, 56);
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_misnamed() {
+        assert_eq!(
+            detect_misnamed(&build_header(WAVE_FORMAT_PCM, 1, 8000, 16, None, 0)),
+            None
+        );
+        assert_eq!(detect_misnamed(b"RF64\xff\xff\xff\xffWAVE"), None);
+        assert_eq!(detect_misnamed(b"RIFX\x00\x00\x10\x00WAVE"), None);
+        assert_eq!(detect_misnamed(b"RIF"), None);
+        assert_eq!(
+            detect_misnamed(b"ID3\x04\x00\x00\x00\x00\x00\x00"),
+            Some("MP3")
+        );
+        assert_eq!(detect_misnamed(&[0xFF, 0xFB, 0x90, 0x64]), Some("MP3"));
+        assert_eq!(detect_misnamed(b"FORM\x00\x00\x10\x00AIFF"), Some("AIFF"));
+        assert_eq!(detect_misnamed(b"fLaC\x00\x00\x00\x22"), Some("FLAC"));
+        assert_eq!(detect_misnamed(b"RIFF\x00\x00\x10\x00AVI "), Some("AVI"));
+        assert_eq!(
+            detect_misnamed(b"hello world!"),
+            Some("unrecognized format")
+        );
+    }
+
+    #[test]
+    fn test_read_header_rejects_non_riff() {
+        assert!(read_header(&b"ID3\x03\x00\x00\x00\x00\x00\x00\x00\x00"[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_header_rejects_huge_fmt_chunk() {
+        let mut bytes = b"RIFF\x00\x00\x00\x00WAVEfmt ".to_vec();
+        bytes.extend(0xFFFF_FFF0u32.to_le_bytes());
+        bytes.extend([0u8; 16]);
+        match read_header(&bytes[..]) {
+            Err(WavStatsError::Corrupt(message)) => {
+                assert!(message.contains("4294967280 bytes"), "{}", message)
+            }
+            other => panic!("expected a corrupt header, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,729 @@
+//! Recursively scan directories for WAV files and compute duration statistics.
+//!
+//! The `wav-files-stats` binary is a thin CLI over this crate: library users
+//! can call [`scan_directory`] and build a [`Summary`] from the result, and
+//! hook their own per-file logic into the scan with [`ScanOptions::on_file`].
+
+pub mod archive;
+pub mod cache;
+pub mod error;
+pub mod header;
+pub mod manifest;
+pub mod repair;
+pub mod resample;
+pub mod samples;
+pub mod scan;
+pub mod stream;
+pub mod summary;
+#[doc(hidden)]
+pub mod test_support;
+pub mod throttle;
+pub mod validate;
+
+pub use error::{FileDetails, Result, WavStatsError};
+pub use scan::{
+    CheckWarning, FileRecord, PathFilter, ScanError, ScanOptions, ScanResult, scan_directory,
+    scan_paths,
+};
+pub use summary::Summary;
+
+use header::WavHeader;
+use hound::{WavReader, WavSpec};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Duration and format details of a single WAV file.
+#[derive(Debug, Clone)]
+pub struct WavInfo {
+    pub path: PathBuf,
+    pub duration: Duration,
+    pub spec: WavSpec,
+    pub source: DurationSource,
+    /// Size of the whole file in bytes, if its metadata could be read.
+    pub file_size: Option<u64>,
+    /// Size of the `data` chunk (the audio payload) in bytes, if known.
+    pub data_size: Option<u64>,
+    /// Modification time of the file (or archive entry), if known.
+    pub modified: Option<SystemTime>,
+}
+
+/// Which part of the header a file's duration was derived from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DurationSource {
+    /// The `data` chunk size divided by the frame size.
+    #[default]
+    DataChunk,
+    /// The sample count declared in the `fact` chunk of a compressed file.
+    FactChunk,
+    /// The 64-bit sizes in the `ds64` chunk of an RF64/BW64 file.
+    Ds64Chunk,
+    /// The `data` (or for compressed codecs `fact`) chunk of a big-endian
+    /// RIFX file.
+    Rifx,
+    /// The whole file size, read as headerless PCM in an assumed
+    /// [`RawFormat`].
+    Raw,
+}
+
+impl fmt::Display for DurationSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DurationSource::DataChunk => write!(f, "data chunk"),
+            DurationSource::FactChunk => write!(f, "fact chunk"),
+            DurationSource::Ds64Chunk => write!(f, "RF64 ds64 chunk"),
+            DurationSource::Rifx => write!(f, "RIFX big-endian header"),
+            DurationSource::Raw => write!(f, "file size as raw PCM"),
+        }
+    }
+}
+
+/// The playback time of `frames` frames at `sample_rate`, rounded to the
+/// nanosecond in integer arithmetic, so that the frame count can be
+/// recovered exactly from the result.
+pub fn frames_duration(frames: u64, sample_rate: u32) -> Duration {
+    if sample_rate == 0 {
+        return Duration::ZERO;
+    }
+    let rate = u128::from(sample_rate);
+    let nanos = (u128::from(frames) * 1_000_000_000 + rate / 2) / rate;
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
+
+/// The number of frames at `sample_rate` in `duration`, the inverse of
+/// [`frames_duration`]: since that rounds to the nanosecond, rounding back
+/// recovers the frame count for any rate below 1 GHz.
+pub fn frame_count(duration: Duration, sample_rate: u32) -> u128 {
+    (duration.as_nanos() * u128::from(sample_rate) + 500_000_000) / 1_000_000_000
+}
+
+/// Reads the header of the WAV file at `path` and returns its duration and
+/// format details. The audio payload itself is not read. If the file opens
+/// but its metadata can't be read, the duration is still returned, with
+/// `file_size` and `modified` left `None`.
+///
+/// hound's `WavReader::len` counts samples across all channels, so the
+/// duration is `len / channels / sample_rate`, not `len / sample_rate`. A
+/// second of stereo audio at 48 kHz holds 96000 samples:
+///
+/// ```
+/// use std::io::Cursor;
+/// use std::path::Path;
+/// use std::time::Duration;
+///
+/// let spec = hound::WavSpec {
+///     channels: 2,
+///     sample_rate: 48000,
+///     bits_per_sample: 16,
+///     sample_format: hound::SampleFormat::Int,
+/// };
+/// let mut wav = Cursor::new(Vec::new());
+/// let mut writer = hound::WavWriter::new(&mut wav, spec)?;
+/// for _ in 0..48000 {
+///     writer.write_sample(0i16)?; // left
+///     writer.write_sample(0i16)?; // right
+/// }
+/// writer.finalize()?;
+///
+/// wav.set_position(0);
+/// let reader = hound::WavReader::new(&mut wav)?;
+/// assert_eq!(reader.len(), 96000);
+/// assert_eq!(reader.duration(), 48000); // frames, i.e. samples per channel
+///
+/// // `calculate_duration` takes a path; the same code reads streams.
+/// wav.set_position(0);
+/// let info = wav_files_stats::duration_from_reader(Path::new("stereo.wav"), wav, None)?;
+/// assert_eq!(info.duration, Duration::from_secs(1));
+/// # Ok::<(), wav_files_stats::WavStatsError>(())
+/// ```
+pub fn calculate_duration(path: &Path) -> Result<WavInfo> {
+    let file = File::open(path)?;
+    let metadata = file.metadata();
+    measure_open_file(path, file, metadata)
+}
+
+fn measure_open_file(
+    path: &Path,
+    file: File,
+    metadata: std::io::Result<std::fs::Metadata>,
+) -> Result<WavInfo> {
+    let metadata = metadata.ok();
+    let file_size = metadata.as_ref().map(|metadata| metadata.len());
+    let modified = metadata.and_then(|metadata| metadata.modified().ok());
+    Ok(WavInfo {
+        modified,
+        ..duration_from_reader(path, BufReader::new(file), file_size)?
+    })
+}
+
+/// Like [`calculate_duration`], for a WAV stream that is not a plain file on
+/// disk, such as an archive entry. `path` only labels the result.
+pub fn duration_from_reader<R: Read + Seek>(
+    path: &Path,
+    mut file: R,
+    file_size: Option<u64>,
+) -> Result<WavInfo> {
+    // Our own parser sees fields hound hides (fact chunk, data size); a
+    // failure here is not fatal since hound reports its own, clearer error.
+    let header = header::read_header(&mut file).ok();
+    file.rewind()?;
+    let details = FileDetails::of_header(header.as_ref(), file_size);
+    measure_reader(path, file, header, file_size).map_err(|e| e.with_details(details))
+}
+
+/// Measures a stream whose header, if our parser could read it, is `header`.
+fn measure_reader<R: Read + Seek>(
+    path: &Path,
+    file: R,
+    header: Option<WavHeader>,
+    file_size: Option<u64>,
+) -> Result<WavInfo> {
+    // Checked before hound so the message is ours for every code path; a
+    // zero here would otherwise divide into an infinite or NaN duration.
+    if let Some(header) = &header {
+        check_spec(header.channels, header.sample_rate)?;
+        check_sub_frame(header)?;
+    }
+
+    // hound cannot open RF64 or RIFX at all, so those are measured from our
+    // header.
+    if let Some(header) = header.filter(|header| header.rf64 || header.big_endian) {
+        return Ok(WavInfo {
+            file_size,
+            ..duration_from_header(path, header)?
+        });
+    }
+
+    let reader = match WavReader::new(file) {
+        Ok(reader) => reader,
+        // hound only accepts PCM and float and rejects compressed codecs
+        // (often while validating their odd bit depths). Those declare their
+        // true length in a `fact` chunk, which we can read ourselves.
+        Err(e) => match header.map(|header| duration_from_fact_chunk(path, header)) {
+            Some(Ok(Some(info))) => return Ok(WavInfo { file_size, ..info }),
+            _ => return Err(e.into()),
+        },
+    };
+    let spec: WavSpec = reader.spec();
+    check_spec(spec.channels, spec.sample_rate)?;
+    let len = reader.len() as u64;
+
+    if len == 0 {
+        return Err(WavStatsError::Empty(FileDetails::default()));
+    }
+
+    // `len()` counts samples across all channels; `duration()` is the
+    // number of frames, which is what maps to playback time.
+    let frames = reader.duration() as u64;
+    let duration = frames_duration(frames, spec.sample_rate);
+
+    Ok(WavInfo {
+        path: path.to_path_buf(),
+        duration,
+        spec,
+        source: DurationSource::DataChunk,
+        file_size,
+        data_size: header.map(|header| header.data_size),
+        modified: None,
+    })
+}
+
+/// Rejects headers whose channel count or sample rate is zero.
+fn check_spec(channels: u16, sample_rate: u32) -> Result<()> {
+    if channels == 0 || sample_rate == 0 {
+        return Err(WavStatsError::ZeroSpec {
+            channels,
+            sample_rate,
+        });
+    }
+    Ok(())
+}
+
+/// A PCM or float file whose `data` chunk is not empty but holds fewer
+/// samples than one frame needs, e.g. a truncated write. Returned as
+/// [`WavStatsError::SubFrame`] by [`calculate_duration`] so callers can tell
+/// it apart from an empty or corrupt file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubFrameError {
+    /// Whole samples in the `data` chunk.
+    pub samples: u64,
+    pub channels: u16,
+    pub details: FileDetails,
+}
+
+impl fmt::Display for SubFrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Sub-frame audio: {} samples is less than one frame of {} channels",
+            self.samples, self.channels
+        )
+    }
+}
+
+impl std::error::Error for SubFrameError {}
+
+/// Rejects uncompressed files whose payload can't hold one complete frame.
+/// Compressed codecs pack samples into blocks, so the arithmetic doesn't apply.
+fn check_sub_frame(header: &WavHeader) -> Result<(), SubFrameError> {
+    let bytes_per_sample = header.bits_per_sample.div_ceil(8).max(1) as u64;
+    let samples = header.data_size / bytes_per_sample;
+    if !header.is_compressed() && header.data_size > 0 && samples < header.channels as u64 {
+        return Err(SubFrameError {
+            samples,
+            channels: header.channels,
+            details: FileDetails::default(),
+        });
+    }
+    Ok(())
+}
+
+/// Computes the duration of an RF64/BW64 or RIFX file, which hound can't
+/// open, from its data size, or for compressed codecs from its sample count.
+fn duration_from_header(path: &Path, header: WavHeader) -> Result<WavInfo> {
+    let (container, source) = if header.rf64 {
+        ("RF64", DurationSource::Ds64Chunk)
+    } else {
+        ("RIFX", DurationSource::Rifx)
+    };
+    let frames = if header.is_compressed() {
+        header
+            .ds64_samples
+            .or(header.fact_samples.map(u64::from))
+            .ok_or_else(|| {
+                WavStatsError::Corrupt(format!(
+                    "Compressed {} file declares no sample count",
+                    container
+                ))
+            })?
+    } else if header.block_align > 0 {
+        header.data_size / header.block_align as u64
+    } else {
+        return Err(WavStatsError::Corrupt(format!(
+            "{} file declares a block alignment of zero",
+            container
+        )));
+    };
+
+    if frames == 0 {
+        return Err(WavStatsError::Empty(FileDetails::default()));
+    }
+
+    Ok(WavInfo {
+        path: path.to_path_buf(),
+        duration: frames_duration(frames, header.sample_rate),
+        spec: WavSpec {
+            channels: header.channels,
+            sample_rate: header.sample_rate,
+            bits_per_sample: header.bits_per_sample,
+            sample_format: if header.format_tag == header::WAVE_FORMAT_IEEE_FLOAT {
+                hound::SampleFormat::Float
+            } else {
+                hound::SampleFormat::Int
+            },
+        },
+        source,
+        file_size: None,
+        data_size: Some(header.data_size),
+        modified: None,
+    })
+}
+
+/// The sample format assumed for headerless PCM files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+}
+
+/// Measures `path` as headerless integer PCM in `format`: every byte of the
+/// file is audio, and a trailing partial frame is ignored. Without metadata
+/// the length is found by seeking to the end of the file instead.
+pub fn raw_duration(path: &Path, format: &RawFormat) -> Result<WavInfo> {
+    check_spec(format.channels, format.sample_rate)?;
+    let metadata = std::fs::metadata(path).ok();
+    let len = match &metadata {
+        Some(metadata) => metadata.len(),
+        None => File::open(path)?.seek(std::io::SeekFrom::End(0))?,
+    };
+    let frame_bytes = (format.bits_per_sample.div_ceil(8).max(1) as u64) * format.channels as u64;
+    let frames = len / frame_bytes;
+    if frames == 0 {
+        return Err(WavStatsError::Empty(FileDetails {
+            file_size: Some(len),
+            channels: Some(format.channels),
+            sample_rate: Some(format.sample_rate),
+            bits_per_sample: Some(format.bits_per_sample),
+        }));
+    }
+
+    Ok(WavInfo {
+        path: path.to_path_buf(),
+        duration: frames_duration(frames, format.sample_rate),
+        spec: WavSpec {
+            channels: format.channels,
+            sample_rate: format.sample_rate,
+            bits_per_sample: format.bits_per_sample,
+            sample_format: hound::SampleFormat::Int,
+        },
+        source: DurationSource::Raw,
+        file_size: metadata.as_ref().map(|metadata| metadata.len()),
+        data_size: Some(len),
+        modified: metadata.and_then(|metadata| metadata.modified().ok()),
+    })
+}
+
+/// Computes the duration of a compressed file from its `fact` chunk.
+/// Returns `None` if the file is not compressed or has no `fact` chunk.
+fn duration_from_fact_chunk(path: &Path, header: WavHeader) -> Result<Option<WavInfo>> {
+    let Some(samples) = header.fact_samples.filter(|_| header.is_compressed()) else {
+        return Ok(None);
+    };
+
+    if samples == 0 {
+        return Err(WavStatsError::Empty(FileDetails::default()));
+    }
+
+    let duration = frames_duration(u64::from(samples), header.sample_rate);
+    Ok(Some(WavInfo {
+        path: path.to_path_buf(),
+        duration,
+        spec: WavSpec {
+            channels: header.channels,
+            sample_rate: header.sample_rate,
+            bits_per_sample: header.bits_per_sample,
+            sample_format: hound::SampleFormat::Int,
+        },
+        source: DurationSource::FactChunk,
+        file_size: None,
+        data_size: Some(header.data_size),
+        modified: None,
+    }))
+}
+
+/// How `format_duration` turns fractional seconds into whole ones.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// Drop the fraction, so 59.9s shows as "59s".
+    Trunc,
+    /// Round to the nearest second, so 59.5s shows as "1m".
+    #[default]
+    Nearest,
+}
+
+/// Formats a `Duration` into a human-readable string like "1h 2m 3s".
+///
+/// The total is rounded to whole seconds before it is split into hours,
+/// minutes and seconds, so a value just under an hour can show as "1h".
+/// A nonzero value that rounds to zero shows as "<1s", so a one-frame clip
+/// reads differently from an empty file.
+pub fn format_duration(duration: Duration, rounding: Rounding) -> String {
+    let total_seconds = match rounding {
+        Rounding::Trunc => duration.as_secs(),
+        Rounding::Nearest => duration
+            .saturating_add(Duration::from_millis(500))
+            .as_secs(),
+    };
+
+    if total_seconds == 0 {
+        return if duration.is_zero() { "0s" } else { "<1s" }.to_string();
+    }
+
+    let hours = total_seconds / 3600;
+    let minutes = (total_seconds % 3600) / 60;
+    let seconds = total_seconds % 60;
+
+    let mut parts = Vec::new();
+    if hours > 0 {
+        parts.push(format!("{}h", hours));
+    }
+    if minutes > 0 {
+        parts.push(format!("{}m", minutes));
+    }
+    if seconds > 0 {
+        parts.push(format!("{}s", seconds));
+    }
+
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_calculate_duration_valid_wav() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let wav_path = dir.path().join("test.wav");
+        let mut file = File::create(&wav_path)?;
+        // Write minimal valid WAV header (44 bytes) + 1 second of silence at 44100 Hz, 1 channel, 16-bit
+        // Note: This is a simplified header; in practice, use hound to generate.
+        let header = include_bytes!("../test_data/minimal_wav_header.bin"); // Assume a test fixture binary
+        file.write_all(header)?;
+        file.write_all(&[0u8; 88200])?; // 1s of 16-bit samples
+
+        let info = calculate_duration(&wav_path)?;
+        assert_eq!(info.duration.as_secs_f64(), 1.0);
+        assert_eq!(info.spec.channels, 1);
+        assert_eq!(info.file_size, Some(44 + 88200));
+        assert_eq!(info.data_size, Some(88200));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_duration_empty_wav() {
+        let dir = TempDir::new().unwrap();
+        let wav_path = dir.path().join("empty.wav");
+        File::create(&wav_path).unwrap();
+
+        let result = calculate_duration(&wav_path);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to read enough bytes."
+        );
+    }
+
+    #[test]
+    fn test_calculate_duration_non_wav() {
+        let dir = TempDir::new().unwrap();
+        let txt_path = dir.path().join("test.txt");
+        File::create(&txt_path).unwrap();
+
+        let result = calculate_duration(&txt_path);
+        assert!(result.is_err()); // hound::open fails on non-WAV
+    }
+
+    #[test]
+    fn test_calculate_duration_stereo_wav() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let wav_path = dir.path().join("stereo.wav");
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        test_support::write_wav(&wav_path, spec, 8000)?;
+
+        // 16000 interleaved samples over two channels is one second of audio.
+        let info = calculate_duration(&wav_path)?;
+        assert_eq!(info.duration.as_secs_f64(), 1.0);
+        assert_eq!(info.spec.channels, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duration_without_metadata() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let wav_path = dir.path().join("unstattable.wav");
+        test_support::write_wav(&wav_path, test_support::pcm16_spec(1, 8000), 8000)?;
+
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let info = measure_open_file(&wav_path, File::open(&wav_path)?, Err(denied))?;
+        assert_eq!(info.duration, Duration::from_secs(1));
+        assert_eq!(info.data_size, Some(16000));
+        assert_eq!(info.file_size, None);
+        assert_eq!(info.modified, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_duration_uses_fact_chunk_for_compressed() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let wav_path = dir.path().join("adpcm.wav");
+        // IMA ADPCM (0x0011), which hound cannot decode: 16000 samples at 8 kHz.
+        let mut bytes = header::tests::build_header(0x0011, 1, 8000, 4, Some(16000), 8000);
+        bytes.extend(std::iter::repeat_n(0u8, 8000));
+        std::fs::write(&wav_path, bytes)?;
+
+        let info = calculate_duration(&wav_path)?;
+        assert_eq!(info.duration, Duration::from_secs(2));
+        assert_eq!(info.source, DurationSource::FactChunk);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_duration_compressed_without_fact_fails() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let wav_path = dir.path().join("adpcm.wav");
+        let mut bytes = header::tests::build_header(0x0011, 1, 8000, 4, None, 8000);
+        bytes.extend(std::iter::repeat_n(0u8, 8000));
+        std::fs::write(&wav_path, bytes)?;
+
+        let err = calculate_duration(&wav_path).unwrap_err();
+        assert!(matches!(err, WavStatsError::Format(_)), "{err}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_duration_rf64() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let wav_path = dir.path().join("field.wav");
+        // 2 s of 16-bit mono at 8 kHz, with the sizes only in the ds64 chunk.
+        let header = header::tests::build_header(header::WAVE_FORMAT_PCM, 1, 8000, 16, None, 0);
+        let mut bytes = header::tests::to_rf64(header, 32000, 16000);
+        bytes.extend(std::iter::repeat_n(0u8, 32000));
+        std::fs::write(&wav_path, bytes)?;
+
+        let info = calculate_duration(&wav_path)?;
+        assert_eq!(info.duration, Duration::from_secs(2));
+        assert_eq!(info.source, DurationSource::Ds64Chunk);
+        assert_eq!(info.data_size, Some(32000));
+        assert_eq!(info.spec.bits_per_sample, 16);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_duration_rifx() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let wav_path = dir.path().join("motorola.wav");
+        // 1.5 s of 16-bit stereo at 44.1 kHz, every field big-endian.
+        let data_size: u32 = 44100 * 4 * 3 / 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFX");
+        bytes.extend_from_slice(&(36 + data_size).to_be_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_be_bytes());
+        bytes.extend_from_slice(&header::WAVE_FORMAT_PCM.to_be_bytes());
+        bytes.extend_from_slice(&2u16.to_be_bytes());
+        bytes.extend_from_slice(&44100u32.to_be_bytes());
+        bytes.extend_from_slice(&(44100u32 * 4).to_be_bytes());
+        bytes.extend_from_slice(&4u16.to_be_bytes());
+        bytes.extend_from_slice(&16u16.to_be_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_size.to_be_bytes());
+        bytes.extend(std::iter::repeat_n(0u8, data_size as usize));
+        std::fs::write(&wav_path, bytes)?;
+
+        let info = calculate_duration(&wav_path)?;
+        assert_eq!(info.duration, Duration::from_millis(1500));
+        assert_eq!(info.source, DurationSource::Rifx);
+        assert_eq!(info.spec.channels, 2);
+        assert_eq!(info.spec.sample_rate, 44100);
+        assert_eq!(info.data_size, Some(data_size as u64));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_duration_flags_sub_frame_files() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let wav_path = dir.path().join("truncated.wav");
+        // One 16-bit sample of a stereo frame.
+        let mut bytes = header::tests::build_header(header::WAVE_FORMAT_PCM, 2, 8000, 16, None, 2);
+        bytes.extend([0u8; 2]);
+        std::fs::write(&wav_path, bytes)?;
+
+        let err = calculate_duration(&wav_path).unwrap_err();
+        assert!(matches!(
+            err,
+            WavStatsError::SubFrame(SubFrameError {
+                samples: 1,
+                channels: 2,
+                ..
+            })
+        ));
+
+        // An empty data chunk is still just empty.
+        let bytes = header::tests::build_header(header::WAVE_FORMAT_PCM, 2, 8000, 16, None, 0);
+        std::fs::write(&wav_path, bytes)?;
+        assert!(matches!(
+            calculate_duration(&wav_path).unwrap_err(),
+            WavStatsError::Empty(_)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_duration_rejects_zero_channels_or_rate() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let cases = [
+            (header::WAVE_FORMAT_PCM, 0, 44100, None, "zero channels"),
+            (header::WAVE_FORMAT_PCM, 2, 0, None, "sample rate of zero"),
+            // The fact-chunk fallback for compressed files divides too.
+            (0x0011, 1, 0, Some(16000), "sample rate of zero"),
+        ];
+        for (format_tag, channels, sample_rate, fact, expected) in cases {
+            let wav_path = dir.path().join("degenerate.wav");
+            let mut bytes =
+                header::tests::build_header(format_tag, channels, sample_rate, 16, fact, 4000);
+            bytes.extend(std::iter::repeat_n(0u8, 4000));
+            std::fs::write(&wav_path, bytes)?;
+
+            let err = calculate_duration(&wav_path).unwrap_err();
+            assert!(matches!(err, WavStatsError::ZeroSpec { .. }), "{err}");
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw_duration() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("dump.wav");
+        // One second of 48 kHz 16-bit stereo, plus a partial frame.
+        std::fs::write(&path, vec![0u8; 192_000 + 3])?;
+        let format = RawFormat {
+            sample_rate: 48000,
+            channels: 2,
+            bits_per_sample: 16,
+        };
+        let info = raw_duration(&path, &format)?;
+        assert_eq!(info.duration, Duration::from_secs(1));
+        assert_eq!(info.source, DurationSource::Raw);
+        assert_eq!(info.file_size, Some(192_003));
+
+        std::fs::write(&path, [0u8; 3])?;
+        assert_eq!(
+            raw_duration(&path, &format).unwrap_err().to_string(),
+            "Empty audio file"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_duration() {
+        let format = |secs| format_duration(Duration::from_secs(secs), Rounding::Trunc);
+        assert_eq!(format(0), "0s");
+        assert_eq!(format(45), "45s");
+        assert_eq!(format(148), "2m 28s");
+        assert_eq!(format(252), "4m 12s");
+        assert_eq!(format(3600 + 120 + 3), "1h 2m 3s");
+        assert_eq!(format(3600), "1h");
+        assert_eq!(format(3603), "1h 3s");
+    }
+
+    #[test]
+    fn test_format_duration_rounding() {
+        let format = |secs, rounding| format_duration(Duration::from_secs_f64(secs), rounding);
+        assert_eq!(format(59.6, Rounding::Trunc), "59s");
+        assert_eq!(format(59.6, Rounding::Nearest), "1m");
+        assert_eq!(format(3599.5, Rounding::Trunc), "59m 59s");
+        assert_eq!(format(3599.5, Rounding::Nearest), "1h");
+        assert_eq!(format(3599.4, Rounding::Nearest), "59m 59s");
+        assert_eq!(format(0.4, Rounding::Nearest), "<1s");
+        assert_eq!(format(0.5, Rounding::Nearest), "1s");
+        assert_eq!(format(0.9, Rounding::Trunc), "<1s");
+        assert_eq!(format(1.0 / 44100.0, Rounding::Nearest), "<1s");
+        assert_eq!(format(0.0, Rounding::Nearest), "0s");
+    }
+}
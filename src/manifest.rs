@@ -0,0 +1,178 @@
+//! A per-file manifest of a dataset for version control: relative path,
+//! duration, format, size, and a SHA-256 of the whole file. The output is
+//! sorted and rounded so that an unchanged tree writes identical bytes.
+
+use crate::WavInfo;
+use anyhow::Context;
+use hound::SampleFormat;
+use rayon::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// One file of the manifest.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ManifestEntry {
+    /// Relative to the scan root, with `/` separators.
+    pub path: String,
+    /// Rounded to microseconds.
+    pub duration_secs: f64,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    /// `int` or `float`.
+    pub sample_format: &'static str,
+    pub file_bytes: u64,
+    /// Lowercase hex SHA-256 of the file's bytes.
+    pub sha256: String,
+}
+
+/// Hashes every file and returns the entries sorted by path.
+pub fn build_manifest(root: &Path, files: &[WavInfo]) -> anyhow::Result<Vec<ManifestEntry>> {
+    let mut entries = files
+        .par_iter()
+        .map(|info| {
+            let (sha256, file_bytes) = hash_file(&info.path)
+                .with_context(|| format!("Failed to hash {}", info.path.display()))?;
+            Ok(ManifestEntry {
+                path: relative_path(root, &info.path),
+                duration_secs: (info.duration.as_secs_f64() * 1e6).round() / 1e6,
+                channels: info.spec.channels,
+                sample_rate: info.spec.sample_rate,
+                bits_per_sample: info.spec.bits_per_sample,
+                sample_format: match info.spec.sample_format {
+                    SampleFormat::Int => "int",
+                    SampleFormat::Float => "float",
+                },
+                file_bytes,
+                sha256,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// `path` relative to `root` with `/` between components, whatever the
+/// platform, or all of `path` if it is not below `root`.
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns the hex SHA-256 and the length of the file at `path`.
+fn hash_file(path: &Path) -> std::io::Result<(String, u64)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut len = 0;
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        len += read as u64;
+    }
+    let hex = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok((hex, len))
+}
+
+/// Writes the entries as a pretty-printed JSON array.
+pub fn write_json<W: std::io::Write>(mut out: W, entries: &[ManifestEntry]) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut out, entries)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Writes the entries as CSV with a header row and durations printed to
+/// six decimals.
+pub fn write_csv<W: std::io::Write>(out: W, entries: &[ManifestEntry]) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record([
+        "path",
+        "duration_secs",
+        "channels",
+        "sample_rate",
+        "bits_per_sample",
+        "sample_format",
+        "file_bytes",
+        "sha256",
+    ])?;
+    for entry in entries {
+        writer.write_record([
+            entry.path.clone(),
+            format!("{:.6}", entry.duration_secs),
+            entry.channels.to_string(),
+            entry.sample_rate.to_string(),
+            entry.bits_per_sample.to_string(),
+            entry.sample_format.to_string(),
+            entry.file_bytes.to_string(),
+            entry.sha256.clone(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::{ScanOptions, scan_directory};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_manifest_is_stable() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        crate::test_support::synthetic_tree(dir.path(), 12)?;
+        let manifest = || -> anyhow::Result<Vec<u8>> {
+            let result = scan_directory(dir.path(), &ScanOptions::default());
+            let mut out = Vec::new();
+            write_csv(&mut out, &build_manifest(dir.path(), &result.files)?)?;
+            Ok(out)
+        };
+        let first = manifest()?;
+        assert_eq!(first, manifest()?);
+
+        let text = String::from_utf8(first)?;
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next(),
+            Some(
+                "path,duration_secs,channels,sample_rate,bits_per_sample,sample_format,file_bytes,sha256"
+            )
+        );
+        let first_row: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(first_row[0], "d0/s0/file_00000.wav");
+        assert_eq!(first_row[1], "0.100000");
+        assert_eq!(first_row[5], "int");
+        assert_eq!(first_row[7].len(), 64);
+        assert_eq!(lines.count(), 11);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_file() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("abc");
+        std::fs::write(&path, b"abc")?;
+        assert_eq!(
+            hash_file(&path)?,
+            (
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+                3
+            )
+        );
+        Ok(())
+    }
+}
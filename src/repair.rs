@@ -0,0 +1,136 @@
+//! Repair of WAV headers whose chunk sizes don't fit the file, as left by a
+//! writer that streamed audio to disk and was stopped before it went back
+//! to fill them in.
+
+use crate::header;
+use anyhow::Context;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Corrected chunk sizes for one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderRepair {
+    pub path: PathBuf,
+    /// The `data` chunk size the header declares: zero, a placeholder, or
+    /// more than the file holds.
+    pub declared_data_size: u64,
+    /// The bytes after the `data` chunk header, in whole frames.
+    pub data_size: u32,
+    /// The file size minus the 8-byte RIFF chunk header.
+    pub riff_size: u32,
+    /// Offset of the audio payload; the `data` size field is just before it.
+    pub data_offset: u64,
+    /// Whether the sizes are written big-endian, for RIFX files.
+    pub big_endian: bool,
+}
+
+/// Checks the header of `path` and returns the sizes to write if its `data`
+/// chunk claims zero bytes or more than the file holds. RF64 files, which
+/// keep their sizes in the `ds64` chunk, and files with no audio after the
+/// header are left alone.
+pub fn plan_header_repair(path: &Path) -> anyhow::Result<Option<HeaderRepair>> {
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let header = header::read_header(BufReader::new(file))?;
+    let available = file_size.saturating_sub(header.data_offset);
+    if header.rf64 || (header.data_size != 0 && header.data_size <= available) {
+        return Ok(None);
+    }
+
+    // A trailing partial frame is left outside the data chunk.
+    let block_align = u64::from(header.block_align.max(1));
+    let data_size = available - available % block_align;
+    if data_size == 0 {
+        return Ok(None);
+    }
+    let (Ok(data_size), Ok(riff_size)) = (u32::try_from(data_size), u32::try_from(file_size - 8))
+    else {
+        anyhow::bail!(
+            "{} bytes do not fit a RIFF header; the file needs RF64",
+            file_size
+        );
+    };
+    Ok(Some(HeaderRepair {
+        path: path.to_path_buf(),
+        declared_data_size: header.data_size,
+        data_size,
+        riff_size,
+        data_offset: header.data_offset,
+        big_endian: header.big_endian,
+    }))
+}
+
+/// Where [`apply_header_repair`] keeps the original of `path`.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+/// Copies the file to its [`backup_path`], then patches the RIFF and `data`
+/// chunk sizes in place. Fails without touching the file if a backup
+/// already exists, so an earlier original is never overwritten.
+pub fn apply_header_repair(repair: &HeaderRepair) -> anyhow::Result<PathBuf> {
+    let backup = backup_path(&repair.path);
+    let mut backup_file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&backup)
+        .with_context(|| format!("Failed to create backup {}", backup.display()))?;
+    io::copy(&mut File::open(&repair.path)?, &mut backup_file)?;
+    backup_file.sync_all()?;
+
+    let to_bytes = if repair.big_endian {
+        u32::to_be_bytes
+    } else {
+        u32::to_le_bytes
+    };
+    let mut file = OpenOptions::new().write(true).open(&repair.path)?;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&to_bytes(repair.riff_size))?;
+    file.seek(SeekFrom::Start(repair.data_offset - 4))?;
+    file.write_all(&to_bytes(repair.data_size))?;
+    file.sync_all()?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculate_duration;
+    use crate::test_support::{pcm16_spec, write_wav};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_repair_unfinalized_header() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("stream.wav");
+        write_wav(&path, pcm16_spec(2, 8000), 8000)?;
+        assert_eq!(plan_header_repair(&path)?, None);
+
+        // What a streaming writer leaves behind: zero sizes and a partial
+        // last frame.
+        let mut bytes = std::fs::read(&path)?;
+        bytes[4..8].fill(0);
+        bytes[40..44].fill(0);
+        bytes.push(0);
+        std::fs::write(&path, &bytes)?;
+        assert!(calculate_duration(&path).is_err());
+
+        let repair = plan_header_repair(&path)?.unwrap();
+        assert_eq!(repair.declared_data_size, 0);
+        assert_eq!(repair.data_size, 32000);
+        assert_eq!(repair.riff_size, 44 + 32000 + 1 - 8);
+
+        let backup = apply_header_repair(&repair)?;
+        assert_eq!(std::fs::read(&backup)?, bytes);
+        assert_eq!(calculate_duration(&path)?.duration, Duration::from_secs(1));
+        assert_eq!(plan_header_repair(&path)?, None);
+        // The first backup is never overwritten.
+        assert!(apply_header_repair(&repair).is_err());
+        assert_eq!(std::fs::read(&backup)?, bytes);
+        Ok(())
+    }
+}
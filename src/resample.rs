@@ -0,0 +1,156 @@
+//! Size estimates for converting every file to one PCM target format, for
+//! planning a transcode. Conversion doesn't change durations, so this is
+//! arithmetic over each file's format and frame count.
+
+use crate::{WavInfo, frame_count};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The header of a converted file: a canonical RIFF, `fmt `, and `data`
+/// chunk header with nothing else.
+pub const CANONICAL_HEADER_BYTES: u64 = 44;
+
+/// The PCM format files are converted to. A `None` field keeps each file's
+/// own value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetFormat {
+    pub sample_rate: Option<u32>,
+    pub bits_per_sample: Option<u16>,
+    pub channels: Option<u16>,
+}
+
+impl fmt::Display for TargetFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.sample_rate {
+            Some(rate) => write!(f, "{} Hz", rate)?,
+            None => write!(f, "source rate")?,
+        }
+        match self.bits_per_sample {
+            Some(bits) => write!(f, ", {}-bit", bits)?,
+            None => write!(f, ", source bit depth")?,
+        }
+        match self.channels {
+            Some(channels) => write!(f, ", {} ch", channels),
+            None => write!(f, ", source channels"),
+        }
+    }
+}
+
+/// The estimated size of one file after conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResampledSize {
+    pub audio_bytes: u64,
+    pub file_bytes: u64,
+}
+
+/// Estimates the size of `info` converted to `target`: its frame count
+/// scaled to the target rate, times the target frame size, plus a canonical
+/// header and the pad byte of an odd-sized `data` chunk.
+pub fn resampled_size(info: &WavInfo, target: &TargetFormat) -> ResampledSize {
+    let rate = info.spec.sample_rate;
+    let target_rate = target.sample_rate.unwrap_or(rate);
+    let frames = if rate == 0 {
+        0
+    } else {
+        (frame_count(info.duration, rate) * u128::from(target_rate) + u128::from(rate) / 2)
+            / u128::from(rate)
+    };
+    let channels = target.channels.unwrap_or(info.spec.channels);
+    let bits = target.bits_per_sample.unwrap_or(info.spec.bits_per_sample);
+    let frame_bytes = u128::from(channels) * u128::from(bits.div_ceil(8));
+    let audio_bytes = u64::try_from(frames * frame_bytes).unwrap_or(u64::MAX);
+    ResampledSize {
+        audio_bytes,
+        file_bytes: CANONICAL_HEADER_BYTES + audio_bytes + audio_bytes % 2,
+    }
+}
+
+/// Sizes of a whole corpus before and after conversion to `target`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResamplePlan {
+    pub target: TargetFormat,
+    pub file_count: usize,
+    /// `data` chunk bytes before conversion, of the files where it is known.
+    pub source_audio_bytes: u64,
+    /// Size on disk before conversion, of the files where it is known.
+    pub source_file_bytes: u64,
+    pub output_audio_bytes: u64,
+    pub output_file_bytes: u64,
+}
+
+impl ResamplePlan {
+    pub fn new(target: TargetFormat) -> Self {
+        ResamplePlan {
+            target,
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, info: &WavInfo) {
+        let output = resampled_size(info, &self.target);
+        self.file_count += 1;
+        self.source_audio_bytes += info.data_size.unwrap_or(0);
+        self.source_file_bytes += info.file_size.unwrap_or(0);
+        self.output_audio_bytes += output.audio_bytes;
+        self.output_file_bytes += output.file_bytes;
+    }
+
+    /// Combines the plans of two disjoint sets of files for the same target.
+    pub fn merge(&mut self, other: &ResamplePlan) {
+        self.file_count += other.file_count;
+        self.source_audio_bytes += other.source_audio_bytes;
+        self.source_file_bytes += other.source_file_bytes;
+        self.output_audio_bytes += other.output_audio_bytes;
+        self.output_file_bytes += other.output_file_bytes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::wav_info;
+
+    #[test]
+    fn test_resampled_size() {
+        // 10 s of 44.1 kHz 16-bit stereo, 1764000 bytes.
+        let info = WavInfo {
+            data_size: Some(1_764_000),
+            file_size: Some(1_764_044),
+            ..wav_info(10, 2)
+        };
+        let target = TargetFormat {
+            sample_rate: Some(16000),
+            bits_per_sample: Some(16),
+            channels: Some(1),
+        };
+        assert_eq!(
+            resampled_size(&info, &target),
+            ResampledSize {
+                audio_bytes: 320_000,
+                file_bytes: 320_044,
+            }
+        );
+        // An unset field keeps the source value.
+        let same = resampled_size(&info, &TargetFormat::default());
+        assert_eq!(same.audio_bytes, 1_764_000);
+
+        // One frame of 8-bit mono is padded to an even chunk size.
+        let odd = WavInfo {
+            duration: crate::frames_duration(1, 44100),
+            ..wav_info(0, 1)
+        };
+        let eight_bit = TargetFormat {
+            bits_per_sample: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(resampled_size(&odd, &eight_bit).file_bytes, 44 + 1 + 1);
+
+        let mut plan = ResamplePlan::new(target);
+        plan.add(&info);
+        plan.merge(&plan.clone());
+        assert_eq!(plan.file_count, 2);
+        assert_eq!(plan.source_file_bytes, 2 * 1_764_044);
+        assert_eq!(plan.output_file_bytes, 2 * 320_044);
+        assert_eq!(target.to_string(), "16000 Hz, 16-bit, 1 ch");
+    }
+}
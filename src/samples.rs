@@ -0,0 +1,676 @@
+//! Checks that decode the audio payload itself rather than just the header.
+//! These read every sample, so they are much slower than a duration scan and
+//! only run when asked for.
+
+use crate::frames_duration;
+use crate::summary::{lossy_path, secs};
+use hound::{SampleFormat, WavReader};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How many offending positions are kept per file.
+const MAX_POSITIONS: usize = 8;
+
+/// Where a bad sample sits in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SamplePosition {
+    /// Zero-based sample frame.
+    pub frame: u64,
+    /// Zero-based channel within the frame.
+    pub channel: u16,
+}
+
+/// A float file holding NaN or infinite samples.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BadFloatSamples {
+    /// The file path, converted lossily if it is not valid UTF-8.
+    pub path: String,
+    pub nan_count: u64,
+    pub infinite_count: u64,
+    /// The first few offending samples, in file order.
+    pub positions: Vec<SamplePosition>,
+}
+
+impl BadFloatSamples {
+    pub fn total(&self) -> u64 {
+        self.nan_count + self.infinite_count
+    }
+}
+
+/// Results of the NaN/Inf check over a whole scan.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FloatSampleCheck {
+    /// Float files that were decoded.
+    pub files_checked: usize,
+    pub total_bad_samples: u64,
+    /// Files with at least one bad sample, sorted by path.
+    pub bad_files: Vec<BadFloatSamples>,
+}
+
+impl FloatSampleCheck {
+    /// Builds the check result from the bad files found in any order.
+    pub fn new(files_checked: usize, mut bad_files: Vec<BadFloatSamples>) -> Self {
+        bad_files.sort_by(|a, b| a.path.cmp(&b.path));
+        FloatSampleCheck {
+            files_checked,
+            total_bad_samples: bad_files.iter().map(BadFloatSamples::total).sum(),
+            bad_files,
+        }
+    }
+}
+
+/// A file's crest factor: the ratio of its peak to its RMS level.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrestFactor {
+    /// The file path, written lossily if it is not valid UTF-8.
+    #[serde(with = "lossy_path")]
+    pub path: PathBuf,
+    /// In dB; for multichannel files, the highest of the channels.
+    pub crest_factor_db: f64,
+}
+
+/// Crest factors over a whole scan.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CrestFactorCheck {
+    /// Files with a defined crest factor; fully silent files have none.
+    pub files_measured: usize,
+    /// The mean of the files' crest factors in dB, if any were measured.
+    pub mean_db: Option<f64>,
+    /// Files below this crest factor are listed in `low_files`.
+    pub threshold_db: f64,
+    /// Files under the threshold, lowest crest factor first.
+    pub low_files: Vec<CrestFactor>,
+}
+
+impl CrestFactorCheck {
+    /// Builds the check result from the sum of every measured file's crest
+    /// factor and the files under the threshold, in any order.
+    pub fn new(
+        files_measured: usize,
+        sum_db: f64,
+        threshold_db: f64,
+        mut low_files: Vec<CrestFactor>,
+    ) -> Self {
+        low_files.sort_by(|a, b| {
+            a.crest_factor_db
+                .total_cmp(&b.crest_factor_db)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        CrestFactorCheck {
+            files_measured,
+            mean_db: (files_measured > 0).then(|| sum_db / files_measured as f64),
+            threshold_db,
+            low_files,
+        }
+    }
+}
+
+/// A file's noise floor: the RMS level of its quietest second.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoiseFloor {
+    /// The file path, written lossily if it is not valid UTF-8.
+    #[serde(with = "lossy_path")]
+    pub path: PathBuf,
+    /// In dBFS, over all channels of the window.
+    pub noise_floor_dbfs: f64,
+}
+
+/// Noise floors over a whole scan.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NoiseFloorCheck {
+    /// Files whose quietest second is digital silence, so they have no
+    /// floor in dB.
+    pub silent_files: usize,
+    /// Every other measured file, quietest first.
+    pub files: Vec<NoiseFloor>,
+}
+
+impl NoiseFloorCheck {
+    /// Builds the check result from the measured files in any order.
+    pub fn new(silent_files: usize, mut files: Vec<NoiseFloor>) -> Self {
+        files.sort_by(|a, b| {
+            a.noise_floor_dbfs
+                .total_cmp(&b.noise_floor_dbfs)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        NoiseFloorCheck {
+            silent_files,
+            files,
+        }
+    }
+
+    /// Files measured, including the silent ones.
+    pub fn files_measured(&self) -> usize {
+        self.silent_files + self.files.len()
+    }
+
+    /// The median floor of the files that have one.
+    pub fn median_dbfs(&self) -> Option<f64> {
+        let n = self.files.len();
+        match n {
+            0 => None,
+            _ if n % 2 == 1 => Some(self.files[n / 2].noise_floor_dbfs),
+            _ => Some(
+                (self.files[n / 2 - 1].noise_floor_dbfs + self.files[n / 2].noise_floor_dbfs) / 2.0,
+            ),
+        }
+    }
+
+    /// How many files fall in each `width_db` wide band, as the band's
+    /// lower edge and its count, quietest band first. Empty bands between
+    /// the quietest and the noisiest file are included.
+    pub fn distribution(&self, width_db: f64) -> Vec<(f64, usize)> {
+        let band = |file: &NoiseFloor| (file.noise_floor_dbfs / width_db).floor();
+        let (Some(first), Some(last)) = (self.files.first().map(band), self.files.last().map(band))
+        else {
+            return Vec::new();
+        };
+        let mut bands: Vec<(f64, usize)> = (0..=(last - first) as usize)
+            .map(|i| ((first + i as f64) * width_db, 0))
+            .collect();
+        for file in &self.files {
+            bands[(band(file) - first) as usize].1 += 1;
+        }
+        bands
+    }
+}
+
+/// Tracks the quietest window of a fixed number of frames as frames arrive.
+#[derive(Debug)]
+struct QuietestWindow {
+    frames: usize,
+    channels: usize,
+    /// Sum of squares of each frame in the window, oldest first.
+    window: VecDeque<f64>,
+    sum: f64,
+    /// Frames in the window with a nonzero sample, so that digital silence
+    /// sums to exactly zero after louder frames have been subtracted out.
+    nonzero: usize,
+    /// The lowest mean square seen over a full window.
+    quietest: Option<f64>,
+}
+
+impl QuietestWindow {
+    fn new(frames: usize, channels: usize) -> Self {
+        QuietestWindow {
+            frames: frames.max(1),
+            channels,
+            // Not preallocated: the window size comes from the header's
+            // sample rate, which a corrupt file can set to billions.
+            window: VecDeque::new(),
+            sum: 0.0,
+            nonzero: 0,
+            quietest: None,
+        }
+    }
+
+    fn push(&mut self, sum_squares: f64) {
+        self.window.push_back(sum_squares);
+        self.sum += sum_squares;
+        if sum_squares > 0.0 {
+            self.nonzero += 1;
+        }
+        if self.window.len() > self.frames {
+            let oldest = self.window.pop_front().unwrap_or_default();
+            self.sum -= oldest;
+            if oldest > 0.0 {
+                self.nonzero -= 1;
+            }
+        }
+        if self.window.len() == self.frames {
+            self.offer();
+        }
+    }
+
+    fn offer(&mut self) {
+        let mean_square = if self.nonzero == 0 {
+            0.0
+        } else {
+            self.sum.max(0.0) / (self.window.len() * self.channels) as f64
+        };
+        self.quietest = Some(
+            self.quietest
+                .map_or(mean_square, |quietest| quietest.min(mean_square)),
+        );
+    }
+
+    /// The quietest window's RMS level in dBFS, negative infinity for
+    /// digital silence, or `None` for a file without frames. A file shorter
+    /// than the window is measured as a whole.
+    fn rms_dbfs(mut self) -> Option<f64> {
+        if self.quietest.is_none() && !self.window.is_empty() {
+            self.offer();
+        }
+        self.quietest.map(|mean_square| 10.0 * mean_square.log10())
+    }
+}
+
+/// A file whose decoded samples last for a different time than its header
+/// says, e.g. because the `data` chunk size claims more than the file holds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecodeMismatch {
+    /// The file path, written lossily if it is not valid UTF-8.
+    #[serde(with = "lossy_path")]
+    pub path: PathBuf,
+    #[serde(with = "secs")]
+    pub header_duration: Duration,
+    #[serde(with = "secs")]
+    pub decoded_duration: Duration,
+}
+
+/// Results of measuring every file by decoding it, over a whole scan.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DecodeCheck {
+    /// Files whose samples were decoded and counted.
+    pub files_decoded: usize,
+    /// Files where the header and the decoded count disagree, sorted by path.
+    pub mismatches: Vec<DecodeMismatch>,
+}
+
+impl DecodeCheck {
+    /// Builds the check result from the mismatches found in any order.
+    pub fn new(files_decoded: usize, mut mismatches: Vec<DecodeMismatch>) -> Self {
+        mismatches.sort_by(|a, b| a.path.cmp(&b.path));
+        DecodeCheck {
+            files_decoded,
+            mismatches,
+        }
+    }
+}
+
+/// Decodes every sample of a WAV file and returns how long the complete
+/// frames that hound yields last, for when the header's length can't be
+/// trusted. Counting stops at the first sample that can't be read, such as
+/// where a truncated file ends.
+pub fn decoded_duration(path: &Path) -> anyhow::Result<Duration> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().take_while(Result::is_ok).count(),
+        SampleFormat::Int => reader.samples::<i32>().take_while(Result::is_ok).count(),
+    };
+    Ok(frames_duration(
+        samples as u64 / u64::from(spec.channels),
+        spec.sample_rate,
+    ))
+}
+
+/// Which measurements [`analyze_samples`] takes in its decoding pass.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SampleChecks {
+    /// Count NaN and infinite samples (float files only).
+    pub float_samples: bool,
+    pub crest_factor: bool,
+    /// Find the quietest 1-second window.
+    pub noise_floor: bool,
+}
+
+/// What one decoding pass over a file found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SampleReport {
+    /// Set if NaN or infinite samples were found.
+    pub bad_float_samples: Option<BadFloatSamples>,
+    /// In dB, the highest of the channels; `None` if not asked for or if
+    /// every channel is silent.
+    pub crest_factor_db: Option<f64>,
+    /// The RMS level of the quietest second in dBFS, negative infinity if
+    /// it is digital silence; `None` if not asked for or the file is empty.
+    pub noise_floor_dbfs: Option<f64>,
+}
+
+/// Peak and sum of squares of one channel's finite samples.
+#[derive(Debug, Clone, Copy, Default)]
+struct Level {
+    peak: f64,
+    sum_squares: f64,
+    count: u64,
+}
+
+impl Level {
+    fn add(&mut self, sample: f64) {
+        self.peak = self.peak.max(sample.abs());
+        self.sum_squares += sample * sample;
+        self.count += 1;
+    }
+
+    fn crest_factor_db(&self) -> Option<f64> {
+        let rms = (self.sum_squares / self.count as f64).sqrt();
+        (self.count > 0 && rms > 0.0).then(|| 20.0 * (self.peak / rms).log10())
+    }
+}
+
+/// Decodes a WAV file once and takes every measurement in `checks`.
+///
+/// The file is not decoded at all if nothing applies to it, e.g. when only
+/// float samples are checked and the file holds integers.
+pub fn analyze_samples(path: &Path, checks: SampleChecks) -> anyhow::Result<SampleReport> {
+    let mut reader = match WavReader::open(path) {
+        Ok(reader) => reader,
+        // hound only decodes little-endian RIFF; say so rather than pass on
+        // its "no RIFF tag found".
+        Err(e) => {
+            let mut magic = [0u8; 4];
+            if File::open(path)
+                .and_then(|mut file| file.read_exact(&mut magic))
+                .is_ok()
+                && &magic == b"RIFX"
+            {
+                anyhow::bail!("samples of big-endian RIFX files can't be decoded");
+            }
+            return Err(e.into());
+        }
+    };
+    let spec = reader.spec();
+    let check_floats = checks.float_samples && spec.sample_format == SampleFormat::Float;
+    if !check_floats && !checks.crest_factor && !checks.noise_floor {
+        return Ok(SampleReport::default());
+    }
+
+    let channels = spec.channels as u64;
+    let mut found = BadFloatSamples {
+        path: path.to_string_lossy().into_owned(),
+        nan_count: 0,
+        infinite_count: 0,
+        positions: Vec::new(),
+    };
+    let mut levels = vec![Level::default(); spec.channels as usize];
+    // Integer samples keep their raw scale, since the crest factor is a
+    // ratio; the noise floor is scaled to full scale.
+    let full_scale = match spec.sample_format {
+        SampleFormat::Float => 1.0,
+        SampleFormat::Int => 2f64.powi(i32::from(spec.bits_per_sample) - 1),
+    };
+    let mut quietest = QuietestWindow::new(spec.sample_rate as usize, spec.channels as usize);
+    let mut frame_squares = 0.0;
+    let mut visit = |index: usize, sample: f64| {
+        let index = index as u64;
+        let channel = index % channels;
+        if checks.noise_floor {
+            if sample.is_finite() {
+                frame_squares += (sample / full_scale).powi(2);
+            }
+            if channel == channels - 1 {
+                quietest.push(frame_squares);
+                frame_squares = 0.0;
+            }
+        }
+        if sample.is_finite() {
+            levels[channel as usize].add(sample);
+            return;
+        }
+        if sample.is_nan() {
+            found.nan_count += 1;
+        } else {
+            found.infinite_count += 1;
+        }
+        if found.positions.len() < MAX_POSITIONS {
+            found.positions.push(SamplePosition {
+                frame: index / channels,
+                channel: channel as u16,
+            });
+        }
+    };
+    match spec.sample_format {
+        SampleFormat::Float => {
+            for (index, sample) in reader.samples::<f32>().enumerate() {
+                visit(index, sample? as f64);
+            }
+        }
+        SampleFormat::Int => {
+            for (index, sample) in reader.samples::<i32>().enumerate() {
+                visit(index, sample? as f64);
+            }
+        }
+    }
+
+    Ok(SampleReport {
+        bad_float_samples: (check_floats && found.total() > 0).then_some(found),
+        crest_factor_db: if checks.crest_factor {
+            levels
+                .iter()
+                .filter_map(Level::crest_factor_db)
+                .max_by(f64::total_cmp)
+        } else {
+            None
+        },
+        noise_floor_dbfs: if checks.noise_floor {
+            quietest.rms_dbfs()
+        } else {
+            None
+        },
+    })
+}
+
+/// Decodes a float WAV file and counts its NaN and infinite samples.
+///
+/// Returns `Ok(None)` for integer files, which cannot hold such values, and
+/// for float files where every sample is finite.
+pub fn find_bad_float_samples(path: &Path) -> anyhow::Result<Option<BadFloatSamples>> {
+    let checks = SampleChecks {
+        float_samples: true,
+        ..Default::default()
+    };
+    Ok(analyze_samples(path, checks)?.bad_float_samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{pcm16_spec, write_wav};
+    use hound::{WavSpec, WavWriter};
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn write_float(path: &Path, samples: &[f32]) -> hound::Result<()> {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = WavWriter::create(path, spec)?;
+        for &sample in samples {
+            writer.write_sample(sample)?;
+        }
+        writer.finalize()
+    }
+
+    #[test]
+    fn test_find_bad_float_samples() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let bad = dir.path().join("bad.wav");
+        write_float(
+            &bad,
+            &[
+                0.0,
+                0.5,
+                f32::NAN,
+                0.1,
+                -0.2,
+                f32::NEG_INFINITY,
+                f32::NAN,
+                1.0,
+            ],
+        )?;
+
+        let found = find_bad_float_samples(&bad)?.unwrap();
+        assert_eq!(found.nan_count, 2);
+        assert_eq!(found.infinite_count, 1);
+        assert_eq!(
+            found.positions,
+            vec![
+                SamplePosition {
+                    frame: 1,
+                    channel: 0
+                },
+                SamplePosition {
+                    frame: 2,
+                    channel: 1
+                },
+                SamplePosition {
+                    frame: 3,
+                    channel: 0
+                },
+            ]
+        );
+
+        let clean = dir.path().join("clean.wav");
+        write_float(&clean, &[0.0, 0.5, -1.0, 1.0])?;
+        assert!(find_bad_float_samples(&clean)?.is_none());
+
+        let int = dir.path().join("int.wav");
+        write_wav(&int, pcm16_spec(1, 8000), 100)?;
+        assert!(find_bad_float_samples(&int)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decoded_duration() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("stereo.wav");
+        write_wav(&path, pcm16_spec(2, 8000), 8000)?;
+        assert_eq!(decoded_duration(&path)?, Duration::from_secs(1));
+
+        // Cut the payload to half a second plus a partial frame; the header
+        // still declares a full second.
+        let file = std::fs::OpenOptions::new().write(true).open(&path)?;
+        file.set_len(44 + 16000 + 2)?;
+        assert_eq!(decoded_duration(&path)?, Duration::from_millis(500));
+        Ok(())
+    }
+
+    #[test]
+    fn test_crest_factor_takes_the_highest_channel() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("mixed.wav");
+        // Left is a square wave (0 dB); right has one peak of 4 in
+        // otherwise +-1 samples: RMS sqrt(31/16), so about 9.17 dB.
+        let mut samples = Vec::new();
+        for frame in 0..16 {
+            let sign = if frame % 2 == 0 { 1.0 } else { -1.0 };
+            samples.extend([0.5 * sign, if frame == 3 { 4.0 } else { sign }]);
+        }
+        write_float(&path, &samples)?;
+        let checks = SampleChecks {
+            crest_factor: true,
+            ..Default::default()
+        };
+
+        let report = analyze_samples(&path, checks)?;
+        let expected = 20.0 * (4.0 / (31.0f64 / 16.0).sqrt()).log10();
+        assert!((report.crest_factor_db.unwrap() - expected).abs() < 1e-9);
+        assert_eq!(report.bad_float_samples, None);
+
+        let silent = dir.path().join("silent.wav");
+        write_wav(&silent, pcm16_spec(1, 8000), 100)?;
+        assert_eq!(analyze_samples(&silent, checks)?.crest_factor_db, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_noise_floor_finds_the_quietest_second() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let write = |name: &str, levels: &[(i16, u32)]| -> hound::Result<PathBuf> {
+            let path = dir.path().join(name);
+            let mut writer = WavWriter::create(&path, pcm16_spec(1, 8000))?;
+            for &(amplitude, frames) in levels {
+                for frame in 0..frames {
+                    writer.write_sample(if frame % 2 == 0 {
+                        amplitude
+                    } else {
+                        -amplitude
+                    })?;
+                }
+            }
+            writer.finalize()?;
+            Ok(path)
+        };
+        let checks = SampleChecks {
+            noise_floor: true,
+            ..Default::default()
+        };
+        let floor = |path: &Path| {
+            analyze_samples(path, checks).map(|report| report.noise_floor_dbfs.unwrap())
+        };
+        let dbfs = |amplitude: f64| 20.0 * (amplitude / 32768.0).log10();
+
+        // 1.5 s loud, then exactly one quiet second.
+        let tail = write("tail.wav", &[(16384, 12000), (164, 8000)])?;
+        assert!((floor(&tail)? - dbfs(164.0)).abs() < 1e-9);
+
+        // A quiet window in the middle is found too, and digital silence
+        // after loud audio is exactly silent.
+        let silence = write("silence.wav", &[(16384, 4000), (0, 8000), (16384, 4000)])?;
+        assert_eq!(floor(&silence)?, f64::NEG_INFINITY);
+
+        // Half a second is measured as a whole.
+        let short = write("short.wav", &[(3277, 4000)])?;
+        assert!((floor(&short)? - dbfs(3277.0)).abs() < 1e-9);
+
+        let empty = write("empty.wav", &[])?;
+        assert_eq!(analyze_samples(&empty, checks)?.noise_floor_dbfs, None);
+
+        // A header claiming billions of frames per second doesn't allocate a
+        // window that size; the few frames present are measured whole.
+        let huge_rate = dir.path().join("huge_rate.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 2_147_483_632,
+            bits_per_sample: 8,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&huge_rate, spec)?;
+        for sample in [64i8, -64, 64, -64] {
+            writer.write_sample(sample)?;
+        }
+        writer.finalize()?;
+        assert!((floor(&huge_rate)? - 20.0 * (64.0f64 / 128.0).log10()).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_noise_floor_distribution() {
+        let file = |path: &str, noise_floor_dbfs| NoiseFloor {
+            path: path.into(),
+            noise_floor_dbfs,
+        };
+        let check = NoiseFloorCheck::new(
+            1,
+            vec![
+                file("c.wav", -45.0),
+                file("a.wav", -72.5),
+                file("b.wav", -61.0),
+                file("d.wav", -70.0),
+            ],
+        );
+        assert_eq!(check.files_measured(), 5);
+        assert_eq!(check.files[0].path, Path::new("a.wav"));
+        assert_eq!(check.median_dbfs(), Some(-65.5));
+        assert_eq!(
+            check.distribution(10.0),
+            vec![(-80.0, 1), (-70.0, 2), (-60.0, 0), (-50.0, 1)]
+        );
+        assert!(NoiseFloorCheck::default().distribution(10.0).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_noise_floor_path_written_lossily() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let path = PathBuf::from(std::ffi::OsString::from_vec(b"caf\xe9.wav".to_vec()));
+        let floor = NoiseFloor {
+            path: path.clone(),
+            noise_floor_dbfs: -60.0,
+        };
+        let json = serde_json::to_value(&floor).unwrap();
+        assert_eq!(json["path"], "caf\u{fffd}.wav");
+        // Only the written form is lossy.
+        assert_eq!(floor.path, path);
+    }
+}
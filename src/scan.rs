@@ -0,0 +1,2441 @@
+//! Directory walking and per-file processing.
+
+use crate::archive;
+use crate::cache::{FileStamp, ScanCache};
+use crate::samples::{
+    self, CrestFactor, CrestFactorCheck, DecodeCheck, DecodeMismatch, FloatSampleCheck, NoiseFloor,
+    NoiseFloorCheck, SampleChecks,
+};
+use crate::stream::RecordSink;
+use crate::summary::{Aggregate, SummaryOptions};
+use crate::throttle::CpuLimiter;
+use crate::validate::{
+    self, ByteRateCheck, StrictWavCheck, Validation, ValidationReport, ValidationRow,
+};
+use crate::{
+    DurationSource, FileDetails, RawFormat, SubFrameError, WavHeader, WavInfo, WavStatsError,
+    calculate_duration, header, raw_duration,
+};
+use anyhow::Context;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BinaryHeap, HashSet};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::WalkDir;
+
+/// A file or directory entry that could not be processed.
+#[derive(Debug)]
+pub enum ScanError {
+    /// The walk itself failed, e.g. an unreadable directory.
+    Entry(WavStatsError),
+    /// A `.wav` file was found but could not be read. The path is kept as a
+    /// `PathBuf` so non-UTF-8 names survive until they are printed, and the
+    /// error is only formatted when printed, so a tree full of broken files
+    /// doesn't build a message string for each one during the scan.
+    File { path: PathBuf, error: WavStatsError },
+    /// A whole archive could not be read (`scan_archives`).
+    Archive { path: PathBuf, error: WavStatsError },
+    /// A `.wav` file that is really another format, found by `verify_magic`.
+    Misnamed {
+        path: PathBuf,
+        detected: &'static str,
+    },
+    /// A file whose header declares no audio at all.
+    Empty { path: PathBuf, details: FileDetails },
+    /// A file whose audio is too short for one complete frame.
+    SubFrame {
+        path: PathBuf,
+        samples: u64,
+        channels: u16,
+        details: FileDetails,
+    },
+    /// A file that opens, but whose channel count or bit depth is
+    /// implausible, which usually means a corrupt or misparsed header.
+    SuspiciousSpec {
+        path: PathBuf,
+        channels: u16,
+        bits_per_sample: u16,
+        details: FileDetails,
+    },
+    /// An error from an earlier scan, replayed along with a summary stored
+    /// by that scan. Only its file and its message, which names the file,
+    /// are kept.
+    Recorded {
+        path: Option<PathBuf>,
+        message: String,
+    },
+}
+
+/// An optional check that failed on a file that otherwise read fine. The
+/// file still counts; only that check's result for it is missing. These are
+/// collected rather than printed so the caller decides whether to show them.
+#[derive(Debug)]
+pub struct CheckWarning {
+    pub path: PathBuf,
+    pub check: FailedCheck,
+    pub error: anyhow::Error,
+}
+
+/// Which step of the scan a [`CheckWarning`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailedCheck {
+    /// Counting decoded samples for [`DurationMethod::Decode`]; the header
+    /// duration is kept.
+    Decode,
+    /// The decoding pass of the sample checks.
+    Samples,
+    /// The header cross-checks of `validate`.
+    Validate,
+    /// The canonical WAV check of `strict_wav`.
+    StrictWav,
+    /// Flushing the cache after recording the file; the file is still
+    /// counted, but the cache on disk may be missing recent entries.
+    Cache,
+}
+
+impl fmt::Display for CheckWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.display();
+        match self.check {
+            FailedCheck::Decode => write!(
+                f,
+                "Failed to decode {}, keeping its header duration: {}",
+                path, self.error
+            ),
+            FailedCheck::Samples => {
+                write!(f, "Failed to check samples of {}: {}", path, self.error)
+            }
+            FailedCheck::Validate => write!(f, "Failed to validate {}: {}", path, self.error),
+            FailedCheck::StrictWav => write!(
+                f,
+                "Failed to check {} against the canonical WAV policy: {}",
+                path, self.error
+            ),
+            FailedCheck::Cache => write!(
+                f,
+                "Failed to flush the cache after reading {}: {}",
+                path, self.error
+            ),
+        }
+    }
+}
+
+impl ScanError {
+    /// The file that failed, if the error is about a specific WAV file.
+    pub fn file_path(&self) -> Option<&Path> {
+        match self {
+            ScanError::Entry(_) => None,
+            ScanError::File { path, .. }
+            | ScanError::Archive { path, .. }
+            | ScanError::Misnamed { path, .. }
+            | ScanError::Empty { path, .. }
+            | ScanError::SubFrame { path, .. }
+            | ScanError::SuspiciousSpec { path, .. } => Some(path),
+            ScanError::Recorded { path, .. } => path.as_deref(),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_message(f, true)
+    }
+}
+
+impl ScanError {
+    /// Writes the message, naming the file after its kind of problem if
+    /// `with_path` is set. Paths are only converted (lossily) here, at the
+    /// final print step.
+    fn write_message(&self, f: &mut fmt::Formatter<'_>, with_path: bool) -> fmt::Result {
+        let path = self.file_path().filter(|_| with_path);
+        let named = |kind: &str| match path {
+            Some(path) => format!("{} {}", kind, path.display()),
+            None => kind.to_string(),
+        };
+        match self {
+            ScanError::Entry(message) => write!(f, "Failed to read entry: {}", message),
+            ScanError::Recorded { message, .. } => write!(f, "{}", message),
+            ScanError::File { error, .. } if path.is_none() => write!(f, "{}", error),
+            ScanError::File { error, .. } => {
+                write!(f, "{}: {}", named("Failed to read WAV file"), error)
+            }
+            ScanError::Archive { error, .. } => {
+                write!(f, "{}: {}", named("Failed to read archive"), error)
+            }
+            ScanError::Misnamed { detected, .. } => write!(
+                f,
+                "{}: named .wav but contains {}",
+                named("Misnamed file"),
+                detected
+            ),
+            ScanError::Empty { details, .. } => {
+                write!(f, "{}: no audio{}", named("Empty file"), details)
+            }
+            ScanError::SubFrame {
+                samples,
+                channels,
+                details,
+                ..
+            } => write!(
+                f,
+                "{}: {} samples, less than one frame of {} channels{}",
+                named("Sub-frame file"),
+                samples,
+                channels,
+                details
+            ),
+            ScanError::SuspiciousSpec {
+                channels,
+                bits_per_sample,
+                details,
+                ..
+            } => write!(
+                f,
+                "{}: {}{}",
+                match path {
+                    Some(path) => format!("Suspicious spec in {}", path.display()),
+                    None => "Suspicious spec".to_string(),
+                },
+                SuspiciousSpecError {
+                    channels: *channels,
+                    bits_per_sample: *bits_per_sample
+                },
+                details
+            ),
+        }
+    }
+}
+
+/// Displays a [`ScanError`] without the path of its file.
+struct WithoutPath<'a>(&'a ScanError);
+
+impl fmt::Display for WithoutPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write_message(f, false)
+    }
+}
+
+/// The channel count above which a spec is suspicious unless
+/// [`ScanOptions::max_channels`] says otherwise.
+pub const DEFAULT_MAX_CHANNELS: u16 = 64;
+
+/// The implausible spec of a file that opened, found by [`report`] and
+/// turned into a [`ScanError::SuspiciousSpec`].
+#[derive(Debug, Clone, Copy)]
+struct SuspiciousSpecError {
+    channels: u16,
+    bits_per_sample: u16,
+}
+
+impl fmt::Display for SuspiciousSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} channel(s) at {} bits per sample is implausible; the header is likely corrupt",
+            self.channels, self.bits_per_sample
+        )
+    }
+}
+
+impl std::error::Error for SuspiciousSpecError {}
+
+/// Checks that a file's channel count is in `1..=max_channels` and, for
+/// PCM and float audio, that its bit depth is 8, 16, 24, or 32 (or 64 for
+/// float). Compressed codecs have their own bit depths, and raw files the
+/// assumed ones, so only their channel count is checked.
+fn check_spec_plausible(info: &WavInfo, max_channels: u16) -> Result<(), SuspiciousSpecError> {
+    let spec = info.spec;
+    let bits_checked = !matches!(info.source, DurationSource::FactChunk | DurationSource::Raw);
+    let bits_ok = match spec.bits_per_sample {
+        8 | 16 | 24 | 32 => true,
+        64 => spec.sample_format == hound::SampleFormat::Float,
+        _ => false,
+    };
+    if (1..=max_channels).contains(&spec.channels) && (bits_ok || !bits_checked) {
+        return Ok(());
+    }
+    Err(SuspiciousSpecError {
+        channels: spec.channels,
+        bits_per_sample: spec.bits_per_sample,
+    })
+}
+
+/// A per-file result in the shape emitted to streaming consumers.
+#[derive(Debug, Serialize)]
+pub struct FileRecord {
+    /// The path, converted lossily if it is not valid UTF-8.
+    pub path: String,
+    /// Set when `path` had to be converted lossily.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub path_lossy: bool,
+    #[serde(flatten)]
+    pub outcome: FileOutcome,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum FileOutcome {
+    Ok {
+        duration_secs: f64,
+        channels: u16,
+        sample_rate: u32,
+        bits_per_sample: u16,
+        duration_source: DurationSource,
+        /// Size on disk, if it could be read.
+        file_bytes: Option<u64>,
+        /// Size of the `data` chunk, if the file has one.
+        data_bytes: Option<u64>,
+    },
+    Err {
+        error: String,
+    },
+}
+
+impl FileRecord {
+    pub fn new(path: &Path, result: Result<&WavInfo, &str>) -> Self {
+        let outcome = match result {
+            Ok(info) => FileOutcome::Ok {
+                duration_secs: info.duration.as_secs_f64(),
+                channels: info.spec.channels,
+                sample_rate: info.spec.sample_rate,
+                bits_per_sample: info.spec.bits_per_sample,
+                duration_source: info.source,
+                file_bytes: info.file_size,
+                data_bytes: info.data_size,
+            },
+            Err(message) => FileOutcome::Err {
+                error: message.to_string(),
+            },
+        };
+        FileRecord {
+            path: path.to_string_lossy().into_owned(),
+            path_lossy: path.to_str().is_none(),
+            outcome,
+        }
+    }
+
+    /// The record of a file that failed, or `None` if the error isn't about
+    /// a specific file.
+    pub fn from_error(error: &ScanError) -> Option<Self> {
+        let path = error.file_path()?;
+        // The path is already in the record, so the message leaves it out.
+        let message = WithoutPath(error).to_string();
+        Some(FileRecord::new(path, Err(&message)))
+    }
+}
+
+/// Everything gathered by a single directory walk.
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    /// Every file read, unless the scan was run with `aggregate_only`.
+    pub files: Vec<WavInfo>,
+    pub errors: Vec<ScanError>,
+    /// Checks that failed on files that were counted anyway, sorted by path.
+    pub check_warnings: Vec<CheckWarning>,
+    /// Running totals over all files read, built as the scan goes.
+    pub aggregate: Aggregate,
+    /// Directories the walk descended into, including the root.
+    pub directories_scanned: usize,
+    /// Directories skipped because a followed symlink led back to a
+    /// directory that was already visited.
+    pub cyclic_symlinks_pruned: usize,
+    /// Files skipped because they are hardlinks to a file already counted.
+    pub hardlink_duplicates: usize,
+    /// Files skipped because they were modified within `skip_recent`.
+    pub recently_modified_skipped: usize,
+    /// Files that were read but whose size and modification time couldn't
+    /// be, e.g. because a stat was denied. Their durations still count.
+    pub metadata_unavailable: usize,
+    /// NaN/Inf samples found in float files, if `check_float_samples` was set.
+    pub float_sample_check: Option<FloatSampleCheck>,
+    /// Crest factors, if `crest_factor_below` was set.
+    pub crest_factor: Option<CrestFactorCheck>,
+    /// Each file's quietest second, if `noise_floor` was set.
+    pub noise_floor: Option<NoiseFloorCheck>,
+    /// Files whose byte rate disagrees with their sample count, if
+    /// `validate` was set.
+    pub byte_rate_check: Option<ByteRateCheck>,
+    /// Files that aren't canonical PCM WAV, if `strict_wav` was set.
+    pub strict_wav_check: Option<StrictWavCheck>,
+    /// Which files passed which checks, if `validation_report` was set.
+    pub validation_report: Option<ValidationReport>,
+    /// Files whose decoded length disagrees with their header, if
+    /// `duration_method` was [`DurationMethod::Decode`].
+    pub decode_check: Option<DecodeCheck>,
+    /// How the files read were drawn, if `sampling` was set.
+    pub sample: Option<SampleStats>,
+    /// Where the time went, if `profile` was set.
+    pub profile: Option<ScanProfile>,
+}
+
+/// Time spent in the phases of one scan. The walk and the header reads
+/// overlap, since files are read while the walk goes on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanProfile {
+    /// From the start of the scan to its end, archives included.
+    pub wall_time: Duration,
+    /// Time spent producing walk entries. Only one thread walks at a time,
+    /// so this is also the walk's share of the wall time.
+    pub walk_time: Duration,
+    /// When the walk ran out of entries, from the start of the scan.
+    pub walk_finished: Duration,
+    /// Time spent reading files, summed over the worker threads. It
+    /// includes waiting on the disk. Archive entries are not counted.
+    pub read_time: Duration,
+    /// From the start of the first file read to the end of the last.
+    pub read_wall_time: Duration,
+    pub files_read: usize,
+}
+
+/// Sums the time spent in one phase across threads.
+struct PhaseTimer {
+    nanos: AtomicU64,
+    calls: AtomicUsize,
+    /// Nanoseconds from the scan's start to the first call and past the last.
+    first_start: AtomicU64,
+    last_end: AtomicU64,
+}
+
+impl PhaseTimer {
+    fn new() -> Self {
+        PhaseTimer {
+            nanos: AtomicU64::new(0),
+            calls: AtomicUsize::new(0),
+            first_start: AtomicU64::new(u64::MAX),
+            last_end: AtomicU64::new(0),
+        }
+    }
+
+    /// Runs `f`, timing it if the scan is profiled, i.e. has an `origin`.
+    fn time<T>(&self, origin: Option<Instant>, f: impl FnOnce() -> T) -> T {
+        let Some(origin) = origin else {
+            return f();
+        };
+        let start = origin.elapsed().as_nanos() as u64;
+        let result = f();
+        let end = origin.elapsed().as_nanos() as u64;
+        self.nanos.fetch_add(end - start, Ordering::Relaxed);
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.first_start.fetch_min(start, Ordering::Relaxed);
+        self.last_end.fetch_max(end, Ordering::Relaxed);
+        result
+    }
+
+    fn total(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+
+    fn span(&self) -> Duration {
+        let end = self.last_end.load(Ordering::Relaxed);
+        Duration::from_nanos(end.saturating_sub(self.first_start.load(Ordering::Relaxed)))
+    }
+}
+
+/// Where a scan takes each file's duration from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DurationMethod {
+    /// The lengths declared in the header, without touching the audio.
+    #[default]
+    Header,
+    /// Count the samples hound decodes, which catches headers that claim
+    /// more audio than the file holds. Reads every sample.
+    Decode,
+}
+
+/// How many of the matched files a sampled scan reads.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleSize {
+    /// Each file is read with this probability, between 0 and 1.
+    Fraction(f64),
+    /// Exactly this many files, or every file if fewer match.
+    Count(usize),
+}
+
+/// Reads only a pseudo-random subset of the matched files.
+///
+/// Each file's rank is a hash of the seed and its path relative to the
+/// root, so the same seed picks the same files on every run, whatever order
+/// the walk visits them in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    pub size: SampleSize,
+    pub seed: u64,
+}
+
+/// The size of a sample and of the set it was drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleStats {
+    pub seed: u64,
+    /// `.wav` files that passed the filter, read or not.
+    pub matched: usize,
+    /// Files selected for reading, including those that failed to read.
+    pub sampled: usize,
+}
+
+/// The pseudo-random rank of `relative` for `seed`: FNV-1a over the path
+/// bytes, finished with the splitmix64 mixer. Unlike the std hashers it is
+/// stable across Rust versions, so samples stay reproducible.
+fn sample_rank(seed: u64, relative: &Path) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325 ^ seed;
+    for &byte in relative.as_os_str().as_encoded_bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+/// Whether `path` is a regular file with a (case-insensitive) `.wav` or
+/// `.wav.gz` extension.
+pub fn is_wav_file(path: &Path) -> bool {
+    path.is_file() && (has_wav_extension(path) || is_gzipped_wav(path))
+}
+
+/// Whether `path` ends in a (case-insensitive) `.wav.gz` extension.
+pub fn is_gzipped_wav(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        name.to_string_lossy()
+            .to_ascii_lowercase()
+            .ends_with(".wav.gz")
+    })
+}
+
+/// Whether `path` ends in a (case-insensitive) `.wav` extension.
+pub fn has_wav_extension(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(OsStr::new("wav")))
+}
+
+/// Glob patterns that select files by their path relative to the scan root.
+///
+/// A file passes if it matches at least one include pattern (or there are
+/// none) and no exclude pattern. `*` stays within one path component while
+/// `**` crosses directories, so `**/mix/*.wav` means "directly in any `mix`
+/// directory".
+#[derive(Debug, Default)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> anyhow::Result<Self> {
+        Ok(PathFilter {
+            include: build_glob_set(include)?,
+            exclude: build_glob_set(exclude)?,
+        })
+    }
+
+    /// Whether a file at `relative` (to the scan root) should be scanned.
+    pub fn matches(&self, relative: &Path) -> bool {
+        let included = self
+            .include
+            .as_ref()
+            .is_none_or(|set| set.is_match(relative));
+        included
+            && !self
+                .exclude
+                .as_ref()
+                .is_some_and(|set| set.is_match(relative))
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> anyhow::Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob pattern {:?}", pattern))?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// Settings that change how files are discovered and read.
+#[derive(Default)]
+pub struct ScanOptions<'a> {
+    pub cache: Option<&'a ScanCache>,
+    /// Only scan `.wav` files whose relative path passes this filter.
+    pub filter: Option<&'a PathFilter>,
+    /// Receives a record for every processed file as soon as it is done.
+    pub sink: Option<&'a RecordSink>,
+    /// Called for every file that was read successfully, for custom
+    /// per-file logic. Files are processed in parallel, so the callback runs
+    /// on several threads at once and sees files in no particular order.
+    pub on_file: Option<&'a (dyn Fn(&WavInfo) + Sync)>,
+    /// Once this is set, no further files are read and the scan returns
+    /// what it has so far, like an aborting [`RecordSink`].
+    pub cancel: Option<&'a AtomicBool>,
+    /// Resolve `..` and symlinks in file paths before using them.
+    pub canonical_paths: bool,
+    /// Descend into symlinked directories, pruning cycles and repeat visits.
+    pub follow_symlinks: bool,
+    /// Count each physical file (device and inode) once. Unix only.
+    pub dedup_inodes: bool,
+    /// Skip files modified less than this long before the scan started,
+    /// which are likely still being written.
+    pub skip_recent: Option<Duration>,
+    /// Log per-file details to stderr.
+    pub verbose: bool,
+    /// The summary extras to gather into [`ScanResult::aggregate`].
+    pub summary: SummaryOptions,
+    /// Only build the aggregate and don't keep every file in
+    /// [`ScanResult::files`], bounding memory on huge trees.
+    pub aggregate_only: bool,
+    /// Also read the `.wav` entries of tar archives found in the tree.
+    pub scan_archives: bool,
+    /// Check each file's magic bytes first and report `.wav` files that are
+    /// really another format as [`ScanError::Misnamed`].
+    pub verify_magic: bool,
+    /// Process matched files in batches of this many, each through the
+    /// parallel pipeline and aggregated before the next batch is walked.
+    /// `None` streams the whole walk into the pipeline at once.
+    pub batch_size: Option<usize>,
+    /// Decode every float file and report NaN and infinite samples in
+    /// [`ScanResult::float_sample_check`]. Archive entries are not checked.
+    pub check_float_samples: bool,
+    /// Measure every file's crest factor for [`ScanResult::crest_factor`],
+    /// listing the files below this many dB. Shares the decoding pass of
+    /// `check_float_samples`. Archive entries are not measured.
+    pub crest_factor_below: Option<f64>,
+    /// Measure every file's noise floor, the RMS level of its quietest
+    /// second, for [`ScanResult::noise_floor`]. Shares the decoding pass of
+    /// `check_float_samples`. Archive entries are not measured.
+    pub noise_floor: bool,
+    /// Cross-check each file's duration against its `fmt` byte rate and
+    /// report disagreements in [`ScanResult::byte_rate_check`]. Archive
+    /// entries are not checked.
+    pub validate: bool,
+    /// Report files that are RF64, RIFX, extensible, or compressed, and so
+    /// not canonical PCM WAV, in [`ScanResult::strict_wav_check`]. They
+    /// are still counted. Archive entries are not checked.
+    pub strict_wav: bool,
+    /// Record which of the enabled checks each file passed in
+    /// [`ScanResult::validation_report`]. Archive entries are not included.
+    pub validation_report: bool,
+    /// Read only a reproducible random subset of the matched files.
+    /// Archive entries are not sampled.
+    pub sampling: Option<Sampling>,
+    /// Measure files whose header can't be parsed as headerless PCM in this
+    /// format instead of failing them.
+    pub raw_format: Option<RawFormat>,
+    /// With `raw_format`, measure every file as headerless PCM, even ones
+    /// with a valid header.
+    pub treat_as_raw: bool,
+    /// Report files with more channels than this as
+    /// [`ScanError::SuspiciousSpec`]; `None` means
+    /// [`DEFAULT_MAX_CHANNELS`].
+    pub max_channels: Option<u16>,
+    /// Time the directory walk and the file reads for
+    /// [`ScanResult::profile`].
+    pub profile: bool,
+    /// With [`DurationMethod::Decode`], plain PCM and float files are
+    /// measured by decoding them, and files whose header disagrees are
+    /// listed in [`ScanResult::decode_check`]. Compressed, RF64, RIFX,
+    /// gzipped, and raw files, and archive entries, keep their header
+    /// duration, as hound can't decode them.
+    pub duration_method: DurationMethod,
+    /// Make the workers sleep after each file to keep the scan's CPU use
+    /// near the limiter's share. Archive entries are not paced.
+    pub cpu_limit: Option<&'a CpuLimiter>,
+}
+
+impl ScanOptions<'_> {
+    /// Whether the scan should stop reading files.
+    fn stopped(&self) -> bool {
+        self.sink.is_some_and(RecordSink::aborted)
+            || self
+                .cancel
+                .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+}
+
+/// Identifies the physical file behind `path`, following symlinks.
+#[cfg(unix)]
+fn inode_key(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode_key(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// Reads the first bytes of `path` and names the format if it isn't a WAV.
+/// Unreadable files are left for the regular read to report.
+fn misnamed_format(path: &Path) -> Option<&'static str> {
+    let mut prefix = Vec::with_capacity(12);
+    std::fs::File::open(path)
+        .ok()?
+        .take(12)
+        .read_to_end(&mut prefix)
+        .ok()?;
+    header::detect_misnamed(&prefix)
+}
+
+/// Collects a [`CheckWarning`] about one file.
+type Warn<'a> = dyn Fn(&Path, FailedCheck, anyhow::Error) + 'a;
+
+/// Parses the header of the file at `path`, up to its `data` chunk.
+fn read_file_header(path: &Path) -> crate::Result<WavHeader> {
+    header::read_header(BufReader::new(File::open(path)?))
+}
+
+/// Reads one WAV file, consulting and updating the cache when one is given.
+/// A failed cache flush is passed to `warn` rather than failing the file.
+fn process_file(path: &Path, options: &ScanOptions, warn: &Warn) -> crate::Result<WavInfo> {
+    let cached = options
+        .cache
+        .and_then(|cache| FileStamp::of(path).map(|stamp| (cache, stamp)));
+    if let Some((cache, stamp)) = cached
+        && let Some(info) = cache.lookup(path, stamp)
+    {
+        if options.verbose {
+            eprintln!("{}: cached (duration from {})", path.display(), info.source);
+        }
+        return Ok(info);
+    }
+
+    let info = measure_file(path)?;
+    if options.verbose {
+        eprintln!("{}: duration from {}", path.display(), info.source);
+    }
+    if let Some((cache, stamp)) = cached
+        && let Err(e) = cache.record(path, stamp, &info)
+    {
+        warn(path, FailedCheck::Cache, e);
+    }
+    Ok(info)
+}
+
+/// Measures a `.wav` or `.wav.gz` file, whichever `path` names; this is
+/// what the cache stores for it.
+pub(crate) fn measure_file(path: &Path) -> crate::Result<WavInfo> {
+    if is_gzipped_wav(path) {
+        archive::gzipped_duration(path)
+    } else {
+        calculate_duration(path)
+    }
+}
+
+/// Reads one file, falling back to `raw_format` for a file whose header
+/// can't be parsed (or, with `treat_as_raw`, going straight to it). Raw
+/// results bypass the cache, so a later run without the assumption reads
+/// the file again.
+fn read_file(path: &Path, options: &ScanOptions, warn: &Warn) -> crate::Result<WavInfo> {
+    let Some(raw_format) = options.raw_format.filter(|_| !is_gzipped_wav(path)) else {
+        return process_file(path, options, warn);
+    };
+    let result = if options.treat_as_raw {
+        raw_duration(path, &raw_format)
+    } else {
+        process_file(path, options, warn).or_else(|e| match read_file_header(path) {
+            Ok(_) => Err(e),
+            Err(_) => raw_duration(path, &raw_format),
+        })
+    };
+    if options.verbose
+        && let Ok(info) = &result
+        && info.source == DurationSource::Raw
+    {
+        eprintln!("{}: duration from {}", path.display(), info.source);
+    }
+    result
+}
+
+/// Walks `root` recursively and processes every `.wav` file in parallel.
+pub fn scan_directory(root: &Path, options: &ScanOptions) -> ScanResult {
+    scan_tree(root, None, options)
+}
+
+/// Processes the `.wav` files among `paths` in parallel, each as soon as
+/// the iterator yields it, so paths can be read from a pipe while another
+/// process is still finding them. Listed directories are counted but not
+/// descended into, and the filter sees each path as listed.
+pub fn scan_paths<'p>(
+    paths: impl Iterator<Item = PathBuf> + Send + 'p,
+    options: &ScanOptions,
+) -> ScanResult {
+    scan_tree(Path::new(""), Some(Box::new(paths)), options)
+}
+
+/// Scans the listed paths, or without a list the whole tree under `root`.
+fn scan_tree<'p>(
+    root: &Path,
+    listed: Option<Box<dyn Iterator<Item = PathBuf> + Send + 'p>>,
+    options: &ScanOptions,
+) -> ScanResult {
+    // With symlinks followed, the same directory can be reached more than
+    // once, or endlessly through a cycle. Directories are tracked by their
+    // canonical path so every repeat visit is pruned. walkdir itself reports
+    // a link back to an ancestor as a loop error, which is counted the same.
+    let mut visited_dirs = HashSet::new();
+    let pruned = AtomicUsize::new(0);
+    let directories = AtomicUsize::new(0);
+    let seen_inodes = Mutex::new(HashSet::new());
+    let hardlink_duplicates = AtomicUsize::new(0);
+    let recently_modified = AtomicUsize::new(0);
+    let metadata_unavailable = AtomicUsize::new(0);
+    let recent_cutoff = options
+        .skip_recent
+        .and_then(|age| SystemTime::now().checked_sub(age));
+    let archives = Mutex::new(Vec::new());
+    let float_files_checked = AtomicUsize::new(0);
+    let bad_float_files = Mutex::new(Vec::new());
+    // Files measured, the sum of their crest factors, and the low ones.
+    let crest_factors = Mutex::new((0, 0.0, Vec::new()));
+    let validation_rows = Mutex::new(Vec::new());
+    let check_warnings = Mutex::new(Vec::new());
+    let warn = |path: &Path, check, error| {
+        check_warnings.lock().unwrap().push(CheckWarning {
+            path: path.to_path_buf(),
+            check,
+            error,
+        });
+    };
+    // Files whose quietest second is digital silence, and the others.
+    let noise_floors = Mutex::new((0, Vec::new()));
+    let byte_rate_files_checked = AtomicUsize::new(0);
+    let byte_rate_mismatches = Mutex::new(Vec::new());
+    let block_align_mismatches = Mutex::new(Vec::new());
+    let strict_files_checked = AtomicUsize::new(0);
+    let nonconforming = Mutex::new(Vec::new());
+    let files_decoded = AtomicUsize::new(0);
+    let decode_mismatches = Mutex::new(Vec::new());
+    let matched = AtomicUsize::new(0);
+    let sampled = AtomicUsize::new(0);
+    let lowest_ranks = Mutex::new(BinaryHeap::new());
+    let origin = options.profile.then(Instant::now);
+    let walk_timer = &PhaseTimer::new();
+    let read_timer = &PhaseTimer::new();
+
+    let mut walk: Box<dyn Iterator<Item = walkdir::Result<walkdir::DirEntry>> + Send + '_> =
+        match listed {
+            // A walk of depth zero yields only the path itself.
+            Some(paths) => Box::new(paths.flat_map(|path| WalkDir::new(path).max_depth(0))),
+            None => Box::new(
+                WalkDir::new(root)
+                    .follow_links(options.follow_symlinks)
+                    .into_iter()
+                    .filter_entry(|entry| {
+                        if !options.follow_symlinks || !entry.file_type().is_dir() {
+                            return true;
+                        }
+                        let Ok(canonical) = std::fs::canonicalize(entry.path()) else {
+                            return true;
+                        };
+                        let first_visit = visited_dirs.insert(canonical);
+                        if !first_visit {
+                            pruned.fetch_add(1, Ordering::Relaxed);
+                        }
+                        first_visit
+                    }),
+            ),
+        };
+    let walk = std::iter::from_fn(move || walk_timer.time(origin, || walk.next()));
+    // Reads one selected `.wav` file and runs the requested checks on it.
+    let check_file = |path: PathBuf| {
+        let file_path = &if options.canonical_paths {
+            std::fs::canonicalize(&path).unwrap_or(path)
+        } else {
+            path
+        };
+        // The checks below read the raw file, which for `.wav.gz` is gzip data.
+        let gzipped = is_gzipped_wav(file_path);
+        let mut row = options
+            .validation_report
+            .then(|| ValidationRow::new(file_path));
+        // Headerless PCM matches no format, so with `raw_format` only files
+        // recognized as something else are misnamed.
+        if options.verify_magic && !gzipped {
+            let misnamed = misnamed_format(file_path).filter(|&detected| {
+                !(options.raw_format.is_some() && detected == header::UNRECOGNIZED_FORMAT)
+            });
+            if let Some(row) = &mut row {
+                row.record(Validation::Magic, misnamed.is_none());
+            }
+            if let Some(detected) = misnamed {
+                let error = ScanError::Misnamed {
+                    path: file_path.to_path_buf(),
+                    detected,
+                };
+                if let Some(sink) = options.sink {
+                    sink.send(&FileRecord::new(file_path, Err(&error.to_string())));
+                }
+                validation_rows.lock().unwrap().extend(row);
+                return Some(Err(error));
+            }
+        }
+        let mut result = read_timer.time(origin, || read_file(file_path, options, &warn));
+        if options.duration_method == DurationMethod::Decode
+            && !gzipped
+            && let Ok(info) = &mut result
+            && info.source == DurationSource::DataChunk
+        {
+            match samples::decoded_duration(file_path) {
+                Ok(decoded) => {
+                    files_decoded.fetch_add(1, Ordering::Relaxed);
+                    if decoded != info.duration {
+                        decode_mismatches.lock().unwrap().push(DecodeMismatch {
+                            path: file_path.to_path_buf(),
+                            header_duration: info.duration,
+                            decoded_duration: decoded,
+                        });
+                        info.duration = decoded;
+                    }
+                }
+                Err(e) => warn(file_path, FailedCheck::Decode, e),
+            }
+        }
+        let result = report(file_path, result, options);
+        if let Some(row) = &mut row {
+            record_read(row, &result);
+        }
+        let raw = result
+            .as_ref()
+            .is_ok_and(|info| info.source == DurationSource::Raw);
+        if let Ok(info) = &result
+            && info.file_size.is_none()
+        {
+            metadata_unavailable.fetch_add(1, Ordering::Relaxed);
+            if options.verbose {
+                eprintln!(
+                    "{}: metadata unavailable, size and date omitted",
+                    file_path.display()
+                );
+            }
+        }
+        let checks = result
+            .as_ref()
+            .ok()
+            .filter(|_| !gzipped && !raw)
+            .map(|info| SampleChecks {
+                float_samples: options.check_float_samples
+                    && info.spec.sample_format == hound::SampleFormat::Float,
+                crest_factor: options.crest_factor_below.is_some(),
+                noise_floor: options.noise_floor,
+            });
+        if let Some(checks) = checks
+            && (checks.float_samples || checks.crest_factor || checks.noise_floor)
+        {
+            if checks.float_samples {
+                float_files_checked.fetch_add(1, Ordering::Relaxed);
+            }
+            match samples::analyze_samples(file_path, checks) {
+                Ok(report) => {
+                    if let Some(row) = row.as_mut().filter(|_| checks.float_samples) {
+                        row.record(Validation::FloatSamples, report.bad_float_samples.is_none());
+                    }
+                    if let Some(found) = report.bad_float_samples {
+                        bad_float_files.lock().unwrap().push(found);
+                    }
+                    if let (Some(db), Some(threshold)) =
+                        (report.crest_factor_db, options.crest_factor_below)
+                    {
+                        let (measured, sum, low) = &mut *crest_factors.lock().unwrap();
+                        *measured += 1;
+                        *sum += db;
+                        if db < threshold {
+                            low.push(CrestFactor {
+                                path: file_path.to_path_buf(),
+                                crest_factor_db: db,
+                            });
+                        }
+                    }
+                    if let Some(dbfs) = report.noise_floor_dbfs {
+                        let (silent, measured) = &mut *noise_floors.lock().unwrap();
+                        if dbfs.is_finite() {
+                            measured.push(NoiseFloor {
+                                path: file_path.to_path_buf(),
+                                noise_floor_dbfs: dbfs,
+                            });
+                        } else {
+                            *silent += 1;
+                        }
+                    }
+                }
+                Err(e) => warn(file_path, FailedCheck::Samples, e),
+            }
+        }
+        // Both header checks share one read of the header.
+        let header = result
+            .as_ref()
+            .ok()
+            .filter(|_| (options.validate || options.strict_wav) && !gzipped && !raw)
+            .map(|info| (info, read_file_header(file_path)));
+        if let Some((info, header)) = &header {
+            if options.validate {
+                byte_rate_files_checked.fetch_add(1, Ordering::Relaxed);
+                match header {
+                    Ok(header) => {
+                        let check = validate::check_header(file_path, info, header);
+                        if let Some(row) = &mut row {
+                            row.record(Validation::ByteRate, check.byte_rate.is_none());
+                            row.record(Validation::BlockAlign, check.block_align.is_none());
+                        }
+                        if let Some(mismatch) = check.byte_rate {
+                            byte_rate_mismatches.lock().unwrap().push(mismatch);
+                        }
+                        if let Some(mismatch) = check.block_align {
+                            block_align_mismatches.lock().unwrap().push(mismatch);
+                        }
+                    }
+                    Err(e) => warn(file_path, FailedCheck::Validate, anyhow::anyhow!("{}", e)),
+                }
+            }
+            if options.strict_wav {
+                strict_files_checked.fetch_add(1, Ordering::Relaxed);
+                match header {
+                    Ok(header) => {
+                        let found = validate::check_strict_wav(file_path, header);
+                        if let Some(row) = &mut row {
+                            row.record(Validation::StrictWav, found.is_none());
+                        }
+                        nonconforming.lock().unwrap().extend(found);
+                    }
+                    Err(e) => warn(file_path, FailedCheck::StrictWav, anyhow::anyhow!("{}", e)),
+                }
+            }
+        }
+        validation_rows.lock().unwrap().extend(row);
+        Some(result)
+    };
+    let process_path = |path: PathBuf| match options.cpu_limit {
+        Some(limiter) => limiter.pace(|| check_file(path)),
+        None => check_file(path),
+    };
+    // Turns one walk entry into a file result; `None` skips the entry.
+    let handle_entry = |entry_result: walkdir::Result<walkdir::DirEntry>| match entry_result {
+        Ok(entry) => {
+            if entry.file_type().is_dir() {
+                directories.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            let selected = options.filter.is_none_or(|filter| filter.matches(relative));
+            if is_wav_file(entry.path()) && selected {
+                if options.stopped() {
+                    return None;
+                }
+                if let Some(cutoff) = recent_cutoff
+                    && let Ok(metadata) = entry.metadata()
+                    && let Ok(modified) = metadata.modified()
+                    && modified > cutoff
+                {
+                    recently_modified.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+                if options.dedup_inodes
+                    && let Some(key) = inode_key(entry.path())
+                    && !seen_inodes.lock().unwrap().insert(key)
+                {
+                    hardlink_duplicates.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+                if let Some(sampling) = options.sampling {
+                    matched.fetch_add(1, Ordering::Relaxed);
+                    let rank = sample_rank(sampling.seed, relative);
+                    match sampling.size {
+                        SampleSize::Fraction(fraction) => {
+                            if rank as f64 > fraction * u64::MAX as f64 {
+                                return None;
+                            }
+                            sampled.fetch_add(1, Ordering::Relaxed);
+                        }
+                        // Only the lowest ranks are kept, and read after the walk.
+                        SampleSize::Count(count) => {
+                            let mut lowest = lowest_ranks.lock().unwrap();
+                            lowest.push((rank, entry.into_path()));
+                            if lowest.len() > count {
+                                lowest.pop();
+                            }
+                            return None;
+                        }
+                    }
+                }
+                process_path(entry.into_path())
+            } else if options.scan_archives && archive::is_archive(entry.path()) {
+                archives.lock().unwrap().push(entry.into_path());
+                None
+            } else {
+                None // Not a selected .wav file, so we skip it.
+            }
+        }
+        Err(e) if e.loop_ancestor().is_some() => {
+            pruned.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+        Err(e) => Some(Err(ScanError::Entry(e.into()))),
+    };
+    let fold = |mut partial: Partial, result| {
+        partial.add(result, options.aggregate_only);
+        partial
+    };
+
+    let Partial {
+        files,
+        errors,
+        aggregate,
+    } = match options.batch_size {
+        None => walk
+            .par_bridge() // Switch to a parallel iterator
+            .filter_map(handle_entry)
+            .fold(|| Partial::new(options), fold)
+            .reduce(|| Partial::new(options), Partial::merge),
+        Some(batch_size) => {
+            // Only `.wav` candidates count towards the batch size; the
+            // directories and other files between them are carried along.
+            let mut total = Partial::new(options);
+            let mut batch = Vec::new();
+            let mut candidates = 0;
+            let mut walk = walk.peekable();
+            while let Some(entry) = walk.next() {
+                if entry.as_ref().is_ok_and(|entry| {
+                    has_wav_extension(entry.path()) || is_gzipped_wav(entry.path())
+                }) {
+                    candidates += 1;
+                }
+                batch.push(entry);
+                if candidates >= batch_size.max(1) || walk.peek().is_none() {
+                    total = total.merge(
+                        std::mem::take(&mut batch)
+                            .into_par_iter()
+                            .filter_map(handle_entry)
+                            .fold(|| Partial::new(options), fold)
+                            .reduce(|| Partial::new(options), Partial::merge),
+                    );
+                    candidates = 0;
+                }
+            }
+            total
+        }
+    };
+
+    // A fixed-size sample is only known once every candidate was ranked.
+    let lowest_ranks = lowest_ranks.into_inner().unwrap();
+    sampled.fetch_add(lowest_ranks.len(), Ordering::Relaxed);
+    let Partial {
+        files,
+        errors,
+        aggregate,
+    } = lowest_ranks
+        .into_vec()
+        .into_par_iter()
+        .filter(|_| !options.stopped())
+        .filter_map(|(_, path)| process_path(path))
+        .fold(|| Partial::new(options), fold)
+        .reduce(|| Partial::new(options), Partial::merge)
+        .merge(Partial {
+            files,
+            errors,
+            aggregate,
+        });
+
+    // Archives are read after the walk: each one sequentially, several at once.
+    let Partial {
+        files,
+        errors,
+        aggregate,
+    } = archives
+        .into_inner()
+        .unwrap()
+        .into_par_iter()
+        .map(|archive| scan_one_archive(root, &archive, options))
+        .reduce(|| Partial::new(options), Partial::merge)
+        .merge(Partial {
+            files,
+            errors,
+            aggregate,
+        });
+
+    let mut check_warnings = check_warnings.into_inner().unwrap();
+    check_warnings.sort_by(|a, b| a.path.cmp(&b.path));
+    ScanResult {
+        files,
+        errors,
+        check_warnings,
+        aggregate,
+        directories_scanned: directories.into_inner(),
+        cyclic_symlinks_pruned: pruned.into_inner(),
+        hardlink_duplicates: hardlink_duplicates.into_inner(),
+        recently_modified_skipped: recently_modified.into_inner(),
+        metadata_unavailable: metadata_unavailable.into_inner(),
+        validation_report: options.validation_report.then(|| {
+            ValidationReport::new(
+                enabled_validations(options),
+                validation_rows.into_inner().unwrap(),
+            )
+        }),
+        float_sample_check: options.check_float_samples.then(|| {
+            FloatSampleCheck::new(
+                float_files_checked.into_inner(),
+                bad_float_files.into_inner().unwrap(),
+            )
+        }),
+        crest_factor: options.crest_factor_below.map(|threshold| {
+            let (measured, sum, low) = crest_factors.into_inner().unwrap();
+            CrestFactorCheck::new(measured, sum, threshold, low)
+        }),
+        noise_floor: options.noise_floor.then(|| {
+            let (silent, measured) = noise_floors.into_inner().unwrap();
+            NoiseFloorCheck::new(silent, measured)
+        }),
+        strict_wav_check: options.strict_wav.then(|| {
+            StrictWavCheck::new(
+                strict_files_checked.into_inner(),
+                nonconforming.into_inner().unwrap(),
+            )
+        }),
+        decode_check: (options.duration_method == DurationMethod::Decode).then(|| {
+            DecodeCheck::new(
+                files_decoded.into_inner(),
+                decode_mismatches.into_inner().unwrap(),
+            )
+        }),
+        sample: options.sampling.map(|sampling| SampleStats {
+            seed: sampling.seed,
+            matched: matched.into_inner(),
+            sampled: sampled.into_inner(),
+        }),
+        byte_rate_check: options.validate.then(|| {
+            ByteRateCheck::new(
+                byte_rate_files_checked.into_inner(),
+                byte_rate_mismatches.into_inner().unwrap(),
+                block_align_mismatches.into_inner().unwrap(),
+            )
+        }),
+        profile: origin.map(|origin| ScanProfile {
+            wall_time: origin.elapsed(),
+            walk_time: walk_timer.total(),
+            walk_finished: Duration::from_nanos(walk_timer.last_end.load(Ordering::Relaxed)),
+            read_time: read_timer.total(),
+            read_wall_time: read_timer.span(),
+            files_read: read_timer.calls.load(Ordering::Relaxed),
+        }),
+    }
+}
+
+/// The columns of the validation report for the checks `options` enables.
+fn enabled_validations(options: &ScanOptions) -> Vec<Validation> {
+    let mut checks = Vec::new();
+    if options.verify_magic {
+        checks.push(Validation::Magic);
+    }
+    checks.extend([Validation::Readable, Validation::Size, Validation::Spec]);
+    if options.validate {
+        checks.extend([Validation::ByteRate, Validation::BlockAlign]);
+    }
+    if options.strict_wav {
+        checks.push(Validation::StrictWav);
+    }
+    if options.check_float_samples {
+        checks.push(Validation::FloatSamples);
+    }
+    checks
+}
+
+/// Records the checks [`report`] makes on every file. They run in order, and
+/// a file failing one isn't given the next.
+fn record_read(row: &mut ValidationRow, result: &Result<WavInfo, ScanError>) {
+    let passed: &[bool] = match result {
+        Ok(_) => &[true, true, true],
+        Err(ScanError::SuspiciousSpec { .. }) => &[true, true, false],
+        Err(ScanError::Empty { .. } | ScanError::SubFrame { .. }) => &[true, false],
+        Err(_) => &[false],
+    };
+    for (&check, &passed) in [Validation::Readable, Validation::Size, Validation::Spec]
+        .iter()
+        .zip(passed)
+    {
+        row.record(check, passed);
+    }
+}
+
+/// Passes a file's result to the sink and the callback, and turns a failure
+/// into a [`ScanError`].
+fn report(
+    path: &Path,
+    result: crate::Result<WavInfo>,
+    options: &ScanOptions,
+) -> Result<WavInfo, ScanError> {
+    let max_channels = options.max_channels.unwrap_or(DEFAULT_MAX_CHANNELS);
+    let suspicious = result
+        .as_ref()
+        .ok()
+        .and_then(|info| check_spec_plausible(info, max_channels).err());
+    // The message is only built here if a socket consumer needs it now.
+    if let Some(sink) = options.sink {
+        let message = match (&result, suspicious) {
+            (Ok(info), None) => Ok(info),
+            (Ok(_), Some(suspicious)) => Err(suspicious.to_string()),
+            (Err(e), _) => Err(e.to_string()),
+        };
+        sink.send(&FileRecord::new(
+            path,
+            message.as_ref().map(|info| *info).map_err(String::as_str),
+        ));
+    }
+    if let (
+        Some(SuspiciousSpecError {
+            channels,
+            bits_per_sample,
+        }),
+        Ok(info),
+    ) = (suspicious, &result)
+    {
+        return Err(ScanError::SuspiciousSpec {
+            path: path.to_path_buf(),
+            channels,
+            bits_per_sample,
+            details: FileDetails::of(info),
+        });
+    }
+    if let (Some(on_file), Ok(info)) = (options.on_file, &result) {
+        on_file(info);
+    }
+    result.map_err(|e| match e {
+        // These carry what was parsed while measuring, for the warning.
+        WavStatsError::SubFrame(SubFrameError {
+            samples,
+            channels,
+            details,
+        }) => ScanError::SubFrame {
+            path: path.to_path_buf(),
+            samples,
+            channels,
+            details,
+        },
+        WavStatsError::Empty(details) => ScanError::Empty {
+            path: path.to_path_buf(),
+            details,
+        },
+        error => ScanError::File {
+            path: path.to_path_buf(),
+            error,
+        },
+    })
+}
+
+/// Reads the `.wav` entries of one archive. The filter sees entry paths as
+/// `relative/archive.tar!/entry.wav`.
+fn scan_one_archive(root: &Path, archive: &Path, options: &ScanOptions) -> Partial {
+    let relative = archive.strip_prefix(root).unwrap_or(archive);
+    let archive = &if options.canonical_paths {
+        std::fs::canonicalize(archive).unwrap_or_else(|_| archive.to_path_buf())
+    } else {
+        archive.to_path_buf()
+    };
+
+    let mut partial = Partial::new(options);
+    let select = |entry: &Path| {
+        let inner = entry.strip_prefix(archive).unwrap_or(entry);
+        options
+            .filter
+            .is_none_or(|filter| filter.matches(&relative.join(inner)))
+    };
+    let outcome = archive::scan_archive(archive, select, |path, result| {
+        if options.verbose
+            && let Ok(info) = &result
+        {
+            eprintln!("{}: duration from {}", path.display(), info.source);
+        }
+        partial.add(report(&path, result, options), options.aggregate_only);
+        !options.stopped()
+    });
+    if let Err(e) = outcome {
+        partial.add(
+            Err(ScanError::Archive {
+                path: archive.to_path_buf(),
+                error: e,
+            }),
+            options.aggregate_only,
+        );
+    }
+    partial
+}
+
+/// What one worker has gathered so far; merged into a single [`ScanResult`].
+struct Partial {
+    files: Vec<WavInfo>,
+    errors: Vec<ScanError>,
+    aggregate: Aggregate,
+}
+
+impl Partial {
+    fn new(options: &ScanOptions) -> Self {
+        Partial {
+            files: Vec::new(),
+            errors: Vec::new(),
+            aggregate: Aggregate::new(&options.summary),
+        }
+    }
+
+    fn add(&mut self, result: Result<WavInfo, ScanError>, aggregate_only: bool) {
+        match result {
+            Ok(info) => {
+                self.aggregate.add(&info);
+                if !aggregate_only {
+                    self.files.push(info);
+                }
+            }
+            Err(error) => self.errors.push(error),
+        }
+    }
+
+    fn merge(mut self, other: Partial) -> Self {
+        self.files.extend(other.files);
+        self.errors.extend(other.errors);
+        self.aggregate = self.aggregate.merge(other.aggregate);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_directory_preserves_non_utf8_paths() -> anyhow::Result<()> {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new()?;
+        // 0xE9 is a Latin-1 "é" and is not valid UTF-8 on its own.
+        let good_path = dir.path().join(OsStr::from_bytes(b"caf\xe9.wav"));
+        let mut file = File::create(&good_path)?;
+        file.write_all(include_bytes!("../test_data/minimal_wav_header.bin"))?;
+        file.write_all(&[0u8; 88200])?;
+        let bad_path = dir.path().join(OsStr::from_bytes(b"bad\xff.WAV"));
+        File::create(&bad_path)?;
+
+        let result = scan_directory(dir.path(), &ScanOptions::default());
+
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.errors.len(), 1);
+        match &result.errors[0] {
+            ScanError::File { path, .. } => assert_eq!(path, &bad_path),
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert!(result.errors[0].to_string().contains("bad\u{FFFD}.WAV"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_errors_keep_their_kind() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        std::fs::write(dir.path().join("junk.wav"), b"not a wav at all")?;
+        let mut zero_channels = crate::header::tests::build_header(
+            crate::header::WAVE_FORMAT_PCM,
+            0,
+            8000,
+            16,
+            None,
+            0,
+        );
+        zero_channels.extend([0u8; 16]);
+        std::fs::write(dir.path().join("mute.wav"), zero_channels)?;
+
+        let mut result = scan_directory(dir.path(), &ScanOptions::default());
+        result
+            .errors
+            .sort_by(|a, b| a.file_path().cmp(&b.file_path()));
+        let [junk, mute] = &result.errors[..] else {
+            panic!("expected two errors, got {:?}", result.errors);
+        };
+        assert!(matches!(
+            junk,
+            ScanError::File {
+                error: WavStatsError::Format(_),
+                ..
+            }
+        ));
+        assert!(
+            junk.to_string()
+                .ends_with("junk.wav: Ill-formed WAVE file: no RIFF tag found")
+        );
+        assert!(matches!(
+            mute,
+            ScanError::File {
+                error: WavStatsError::ZeroSpec { channels: 0, .. },
+                ..
+            }
+        ));
+        assert!(
+            mute.to_string()
+                .ends_with("mute.wav: Invalid format: header declares zero channels")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_paths_reads_only_listed_files() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let sub = dir.path().join("sub");
+        std::fs::create_dir(&sub)?;
+        let listed = dir.path().join("listed.wav");
+        crate::test_support::write_wav(&listed, crate::test_support::pcm16_spec(1, 8000), 8000)?;
+        // Not listed, so not read even though its directory is.
+        crate::test_support::write_wav(
+            &sub.join("unlisted.wav"),
+            crate::test_support::pcm16_spec(1, 8000),
+            8000,
+        )?;
+        std::fs::write(dir.path().join("notes.txt"), "not audio")?;
+
+        let paths = vec![
+            listed.clone(),
+            sub.clone(),
+            dir.path().join("notes.txt"),
+            dir.path().join("missing.wav"),
+        ];
+        let result = scan_paths(paths.into_iter(), &ScanOptions::default());
+
+        let read: Vec<&Path> = result
+            .files
+            .iter()
+            .map(|info| info.path.as_path())
+            .collect();
+        assert_eq!(read, vec![listed.as_path()]);
+        assert_eq!(result.directories_scanned, 1);
+        assert_eq!(result.errors.len(), 1);
+        assert!(matches!(&result.errors[0], ScanError::Entry(_)));
+        assert_eq!(
+            crate::Summary::from_scan(&result).total_duration,
+            Duration::from_secs(1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_near_empty_warnings_carry_details() -> anyhow::Result<()> {
+        use crate::header::{WAVE_FORMAT_PCM, tests::build_header};
+
+        let dir = TempDir::new()?;
+        std::fs::write(
+            dir.path().join("empty.wav"),
+            build_header(WAVE_FORMAT_PCM, 2, 44100, 16, None, 0),
+        )?;
+        let mut sub_frame = build_header(WAVE_FORMAT_PCM, 2, 44100, 16, None, 2);
+        sub_frame.extend([0u8; 2]);
+        std::fs::write(dir.path().join("sub_frame.wav"), sub_frame)?;
+        std::fs::write(dir.path().join("junk.wav"), b"junk")?;
+
+        let result = scan_directory(dir.path(), &ScanOptions::default());
+        let mut messages: Vec<String> = result.errors.iter().map(ToString::to_string).collect();
+        messages.sort();
+        let name = |file: &str| dir.path().join(file).display().to_string();
+        assert_eq!(
+            messages,
+            vec![
+                format!(
+                    "Empty file {}: no audio (44 bytes, 2 ch, 44100 Hz, 16-bit)",
+                    name("empty.wav")
+                ),
+                // A file that can't be parsed has no details to add.
+                format!(
+                    "Failed to read WAV file {}: Ill-formed WAVE file: no RIFF tag found",
+                    name("junk.wav")
+                ),
+                format!(
+                    "Sub-frame file {}: 1 samples, less than one frame of 2 channels (46 bytes, 2 ch, 44100 Hz, 16-bit)",
+                    name("sub_frame.wav")
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_near_empty_entries_carry_parsed_details() -> anyhow::Result<()> {
+        use crate::header::{WAVE_FORMAT_PCM, tests::build_header};
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+
+        let dir = TempDir::new()?;
+        let empty = build_header(WAVE_FORMAT_PCM, 2, 44100, 16, None, 0);
+        let mut builder = tar::Builder::new(File::create(dir.path().join("set.tar"))?);
+        let mut entry = tar::Header::new_gnu();
+        entry.set_size(empty.len() as u64);
+        entry.set_cksum();
+        builder.append_data(&mut entry, "empty.wav", &empty[..])?;
+        builder.into_inner()?;
+        let mut sub_frame = build_header(WAVE_FORMAT_PCM, 2, 8000, 16, None, 2);
+        sub_frame.extend([0u8; 2]);
+        let mut gzipped = GzEncoder::new(
+            File::create(dir.path().join("short.wav.gz"))?,
+            Compression::default(),
+        );
+        gzipped.write_all(&sub_frame)?;
+        gzipped.finish()?;
+
+        let options = ScanOptions {
+            scan_archives: true,
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        let details = |name: &str| {
+            result
+                .errors
+                .iter()
+                .find_map(|error| match error {
+                    ScanError::Empty { path, details }
+                    | ScanError::SubFrame { path, details, .. }
+                        if path.ends_with(name) =>
+                    {
+                        Some(*details)
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+        // The archive entry's size is its own, not the archive's.
+        assert_eq!(
+            details("empty.wav"),
+            FileDetails {
+                file_size: Some(44),
+                channels: Some(2),
+                sample_rate: Some(44100),
+                bits_per_sample: Some(16),
+            }
+        );
+        // The gzipped file's header comes from the decompressed bytes.
+        let short = details("short.wav.gz");
+        assert_eq!((short.channels, short.sample_rate), (Some(2), Some(8000)));
+
+        // A file record carries the path itself, so its message leaves it out.
+        let record = serde_json::to_value(
+            result
+                .errors
+                .iter()
+                .filter_map(FileRecord::from_error)
+                .find(|record| record.path.ends_with("empty.wav"))
+                .unwrap(),
+        )?;
+        assert_eq!(
+            record["error"],
+            "Empty file: no audio (44 bytes, 2 ch, 44100 Hz, 16-bit)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancelled_scan_reads_nothing_more() -> anyhow::Result<()> {
+        use crate::test_support::{pcm16_spec, write_wav};
+
+        let dir = TempDir::new()?;
+        write_wav(&dir.path().join("a.wav"), pcm16_spec(1, 8000), 8000)?;
+        let cancel = AtomicBool::new(true);
+        let options = ScanOptions {
+            cancel: Some(&cancel),
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        assert!(result.files.is_empty());
+        assert!(result.errors.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_canonical_paths() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        File::create(dir.path().join("broken.wav"))?;
+        let root = dir.path().join("sub").join("..");
+
+        let result = scan_directory(&root, &ScanOptions::default());
+        let ScanError::File { path, .. } = &result.errors[0] else {
+            panic!("expected a file error");
+        };
+        assert!(path.starts_with(&root));
+
+        let result = scan_directory(
+            &root,
+            &ScanOptions {
+                canonical_paths: true,
+                ..Default::default()
+            },
+        );
+        let ScanError::File { path, .. } = &result.errors[0] else {
+            panic!("expected a file error");
+        };
+        assert_eq!(path, &dir.path().canonicalize()?.join("broken.wav"));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_directory_prunes_symlink_cycles() -> anyhow::Result<()> {
+        use crate::test_support::{pcm16_spec, write_wav};
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new()?;
+        let sub = dir.path().join("sub");
+        std::fs::create_dir(&sub)?;
+        write_wav(&sub.join("a.wav"), pcm16_spec(1, 8000), 800)?;
+        // A link back to the root (a cycle) and a second route into `sub`.
+        symlink(dir.path(), sub.join("loop"))?;
+        symlink(&sub, dir.path().join("alias"))?;
+
+        let result = scan_directory(dir.path(), &ScanOptions::default());
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.cyclic_symlinks_pruned, 0);
+
+        let options = ScanOptions {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.cyclic_symlinks_pruned, 2);
+        // The root and `sub`; pruned routes don't count as scanned.
+        assert_eq!(result.directories_scanned, 2);
+        assert!(result.errors.is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_directory_dedups_hardlinks() -> anyhow::Result<()> {
+        use crate::test_support::{pcm16_spec, write_wav};
+
+        let dir = TempDir::new()?;
+        write_wav(&dir.path().join("a.wav"), pcm16_spec(1, 8000), 800)?;
+        write_wav(&dir.path().join("b.wav"), pcm16_spec(1, 8000), 800)?;
+        std::fs::hard_link(dir.path().join("a.wav"), dir.path().join("a-link.wav"))?;
+
+        let result = scan_directory(dir.path(), &ScanOptions::default());
+        assert_eq!(result.files.len(), 3);
+        assert_eq!(result.hardlink_duplicates, 0);
+
+        let options = ScanOptions {
+            dedup_inodes: true,
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        assert_eq!(result.files.len(), 2);
+        assert_eq!(result.hardlink_duplicates, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_skips_recent_files() -> anyhow::Result<()> {
+        use crate::test_support::{pcm16_spec, write_wav};
+
+        let dir = TempDir::new()?;
+        let old = dir.path().join("old.wav");
+        write_wav(&old, pcm16_spec(1, 8000), 800)?;
+        File::options()
+            .write(true)
+            .open(&old)?
+            .set_modified(SystemTime::now() - Duration::from_secs(3600))?;
+        write_wav(&dir.path().join("new.wav"), pcm16_spec(1, 8000), 800)?;
+
+        let options = ScanOptions {
+            skip_recent: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.files[0].path, old);
+        assert_eq!(result.recently_modified_skipped, 1);
+        assert!(result.errors.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_decodes_durations() -> anyhow::Result<()> {
+        use crate::test_support::{pcm16_spec, write_wav};
+
+        let dir = TempDir::new()?;
+        write_wav(&dir.path().join("intact.wav"), pcm16_spec(1, 8000), 8000)?;
+        // The header of this one still declares a second after truncation.
+        let truncated = dir.path().join("truncated.wav");
+        write_wav(&truncated, pcm16_spec(1, 8000), 8000)?;
+        File::options()
+            .write(true)
+            .open(&truncated)?
+            .set_len(44 + 4000)?;
+
+        let header = scan_directory(dir.path(), &ScanOptions::default());
+        assert_eq!(
+            crate::Summary::from_scan(&header).total_duration,
+            Duration::from_secs(2)
+        );
+        assert!(header.decode_check.is_none());
+
+        let options = ScanOptions {
+            duration_method: DurationMethod::Decode,
+            ..Default::default()
+        };
+        let decoded = scan_directory(dir.path(), &options);
+        assert_eq!(
+            crate::Summary::from_scan(&decoded).total_duration,
+            Duration::from_millis(1250)
+        );
+        let check = decoded.decode_check.unwrap();
+        assert_eq!(check.files_decoded, 2);
+        assert_eq!(
+            check.mismatches,
+            vec![DecodeMismatch {
+                path: truncated.clone(),
+                header_duration: Duration::from_secs(1),
+                decoded_duration: Duration::from_millis(250),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_filter() -> anyhow::Result<()> {
+        let filter = PathFilter::new(&["**/mix/*.wav".to_string()], &["**/old/**".to_string()])?;
+        assert!(filter.matches(Path::new("album/mix/a.wav")));
+        assert!(filter.matches(Path::new("mix/a.wav")));
+        assert!(!filter.matches(Path::new("album/mix/stems/a.wav")));
+        assert!(!filter.matches(Path::new("album/raw/a.wav")));
+        // Exclude applies after include.
+        assert!(!filter.matches(Path::new("old/mix/a.wav")));
+
+        let filter = PathFilter::new(&[], &["*.tmp.wav".to_string()])?;
+        assert!(filter.matches(Path::new("sub/a.wav")));
+        assert!(!filter.matches(Path::new("a.tmp.wav")));
+
+        assert!(PathFilter::new(&["[".to_string()], &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_applies_filter() -> anyhow::Result<()> {
+        use crate::test_support::{pcm16_spec, write_wav};
+
+        let dir = TempDir::new()?;
+        for sub in ["mix", "raw"] {
+            std::fs::create_dir(dir.path().join(sub))?;
+            write_wav(
+                &dir.path().join(sub).join("a.wav"),
+                pcm16_spec(1, 8000),
+                800,
+            )?;
+        }
+
+        let filter = PathFilter::new(&["mix/*.wav".to_string()], &[])?;
+        let options = ScanOptions {
+            filter: Some(&filter),
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        assert_eq!(result.files.len(), 1);
+        assert!(result.files[0].path.ends_with("mix/a.wav"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregate_only_matches_collected_summary() -> anyhow::Result<()> {
+        use crate::Summary;
+
+        let dir = TempDir::new()?;
+        crate::test_support::synthetic_tree(dir.path(), 60)?;
+        std::fs::write(dir.path().join("junk.wav"), b"not a wav")?;
+        let summary = SummaryOptions {
+            histogram_buckets: Some(5),
+            allowed_rates: Some(vec![8000]),
+            ..Default::default()
+        };
+
+        let collected = scan_directory(
+            dir.path(),
+            &ScanOptions {
+                summary: summary.clone(),
+                ..Default::default()
+            },
+        );
+        let streamed = scan_directory(
+            dir.path(),
+            &ScanOptions {
+                summary: summary.clone(),
+                aggregate_only: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(collected.files.len(), 60);
+        assert!(streamed.files.is_empty());
+        assert_eq!(streamed.errors.len(), 1);
+
+        // Directory counts come from the walk, not the files.
+        let expected = Summary {
+            directories_scanned: collected.directories_scanned,
+            ..Summary::new(&collected.files, &collected.errors, &summary)
+        };
+        let actual = Summary::from_scan(&streamed);
+        assert_eq!(
+            serde_json::to_value(&actual)?,
+            serde_json::to_value(&expected)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_batched_scan_matches_unbatched() -> anyhow::Result<()> {
+        use crate::Summary;
+
+        let dir = TempDir::new()?;
+        crate::test_support::synthetic_tree(dir.path(), 60)?;
+        std::fs::write(dir.path().join("junk.wav"), b"not a wav")?;
+        let summary = SummaryOptions {
+            histogram_buckets: Some(5),
+            ..Default::default()
+        };
+
+        let unbatched = scan_directory(
+            dir.path(),
+            &ScanOptions {
+                summary: summary.clone(),
+                ..Default::default()
+            },
+        );
+        for batch_size in [1, 7, 1000] {
+            let batched = scan_directory(
+                dir.path(),
+                &ScanOptions {
+                    summary: summary.clone(),
+                    batch_size: Some(batch_size),
+                    ..Default::default()
+                },
+            );
+            assert_eq!(batched.files.len(), 60);
+            assert_eq!(batched.errors.len(), 1);
+            assert_eq!(
+                serde_json::to_value(Summary::from_scan(&batched))?,
+                serde_json::to_value(Summary::from_scan(&unbatched))?
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sampled_scan_is_reproducible() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        crate::test_support::synthetic_tree(dir.path(), 40)?;
+        let sampled = |size, seed, aggregate_only| {
+            scan_directory(
+                dir.path(),
+                &ScanOptions {
+                    sampling: Some(Sampling { size, seed }),
+                    aggregate_only,
+                    ..Default::default()
+                },
+            )
+        };
+        let paths = |result: &ScanResult| {
+            let mut paths: Vec<PathBuf> =
+                result.files.iter().map(|info| info.path.clone()).collect();
+            paths.sort();
+            paths
+        };
+
+        let first = sampled(SampleSize::Count(10), 7, false);
+        assert_eq!(
+            first.sample,
+            Some(SampleStats {
+                seed: 7,
+                matched: 40,
+                sampled: 10
+            })
+        );
+        assert_eq!(
+            paths(&first),
+            paths(&sampled(SampleSize::Count(10), 7, false))
+        );
+        assert_ne!(
+            paths(&first),
+            paths(&sampled(SampleSize::Count(10), 8, false))
+        );
+        assert_eq!(
+            crate::Summary::from_scan(&sampled(SampleSize::Count(10), 7, true)).file_count,
+            10
+        );
+        assert_eq!(sampled(SampleSize::Count(100), 7, false).files.len(), 40);
+
+        let all = sampled(SampleSize::Fraction(1.0), 7, false);
+        assert_eq!(
+            all.sample,
+            Some(SampleStats {
+                seed: 7,
+                matched: 40,
+                sampled: 40
+            })
+        );
+        let half = sampled(SampleSize::Fraction(0.5), 7, false).sample.unwrap();
+        assert_eq!(half.matched, 40);
+        assert!(half.sampled > 0 && half.sampled < 40, "{}", half.sampled);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_profile() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        crate::test_support::synthetic_tree(dir.path(), 20)?;
+        assert!(
+            scan_directory(dir.path(), &ScanOptions::default())
+                .profile
+                .is_none()
+        );
+
+        let options = ScanOptions {
+            profile: true,
+            ..Default::default()
+        };
+        let profile = scan_directory(dir.path(), &options).profile.unwrap();
+        assert_eq!(profile.files_read, 20);
+        assert!(profile.read_time > Duration::ZERO);
+        assert!(profile.walk_time > Duration::ZERO);
+        assert!(profile.walk_finished <= profile.wall_time);
+        assert!(profile.read_wall_time <= profile.wall_time);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_calls_on_file() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        crate::test_support::synthetic_tree(dir.path(), 10)?;
+        std::fs::write(dir.path().join("junk.wav"), b"not a wav")?;
+
+        let seen = Mutex::new(Vec::new());
+        let on_file = |info: &WavInfo| seen.lock().unwrap().push(info.duration);
+        let options = ScanOptions {
+            on_file: Some(&on_file),
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+
+        let mut seen = seen.into_inner().unwrap();
+        let mut expected: Vec<Duration> = result.files.iter().map(|info| info.duration).collect();
+        seen.sort();
+        expected.sort();
+        // Only successfully read files reach the callback.
+        assert_eq!(seen.len(), 10);
+        assert_eq!(seen, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_reads_archives() -> anyhow::Result<()> {
+        use crate::test_support::{pcm16_spec, write_wav};
+
+        let dir = TempDir::new()?;
+        let staging = TempDir::new()?;
+        write_wav(&staging.path().join("a.wav"), pcm16_spec(1, 8000), 8000)?;
+        let mut builder = tar::Builder::new(File::create(dir.path().join("set.tar"))?);
+        builder.append_path_with_name(staging.path().join("a.wav"), "inner/a.wav")?;
+        builder.into_inner()?;
+        std::fs::write(dir.path().join("broken.tgz"), b"not gzip")?;
+
+        let result = scan_directory(dir.path(), &ScanOptions::default());
+        assert!(result.files.is_empty());
+        assert!(result.errors.is_empty());
+
+        let options = ScanOptions {
+            scan_archives: true,
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(
+            result.files[0].path,
+            dir.path().join("set.tar!/inner/a.wav")
+        );
+        assert_eq!(result.files[0].duration, Duration::from_secs(1));
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(
+            result.errors[0].file_path(),
+            Some(dir.path().join("broken.tgz").as_path())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_totals_match_loose_files() -> anyhow::Result<()> {
+        use crate::summary::Summary;
+        use crate::test_support::{pcm16_spec, write_wav};
+        use flate2::write::GzEncoder;
+
+        // Frame counts that don't divide their rates, so every duration is
+        // rounded and a floating-point sum would drift.
+        const RATES: [u32; 3] = [44100, 22050, 48000];
+        let loose = TempDir::new()?;
+        let mixed = TempDir::new()?;
+        let mut frames_by_rate = [0u64; 3];
+        let mut tar = tar::Builder::new(GzEncoder::new(
+            File::create(mixed.path().join("half.tar.gz"))?,
+            flate2::Compression::fast(),
+        ));
+        for i in 0..40 {
+            let rate = RATES[i % RATES.len()];
+            let frames = 1001 + 37 * i as u32;
+            frames_by_rate[i % RATES.len()] += u64::from(frames);
+            let name = format!("f{i:02}.wav");
+            write_wav(
+                &loose.path().join(&name),
+                pcm16_spec(1 + (i % 2) as u16, rate),
+                frames,
+            )?;
+            // Half of the corpus is read from disk, the other half from the archive.
+            if i % 2 == 0 {
+                std::fs::copy(loose.path().join(&name), mixed.path().join(&name))?;
+            } else {
+                tar.append_path_with_name(loose.path().join(&name), format!("inner/{name}"))?;
+            }
+        }
+        tar.into_inner()?.finish()?;
+
+        let options = ScanOptions {
+            scan_archives: true,
+            aggregate_only: true,
+            ..Default::default()
+        };
+        let from_disk = Summary::from_scan(&scan_directory(loose.path(), &options));
+        let from_both = Summary::from_scan(&scan_directory(mixed.path(), &options));
+        let exact: Duration = RATES
+            .iter()
+            .zip(frames_by_rate)
+            .map(|(&rate, frames)| crate::frames_duration(frames, rate))
+            .sum();
+        assert_eq!(from_both.file_count, 40);
+        assert_eq!(from_disk.total_duration, exact);
+        assert_eq!(from_both.total_duration, exact);
+        assert_eq!(from_both.average_duration, from_disk.average_duration);
+        assert_eq!(
+            from_both.total_channel_seconds,
+            from_disk.total_channel_seconds
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_verify_magic() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        std::fs::write(
+            dir.path().join("song.wav"),
+            b"ID3\x04\x00\x00\x00\x00\x00\x00 mp3 bytes",
+        )?;
+        std::fs::write(
+            dir.path().join("corrupt.wav"),
+            b"RIFF\x10\x00\x00\x00WAVEjunk",
+        )?;
+
+        let result = scan_directory(dir.path(), &ScanOptions::default());
+        assert!(
+            result
+                .errors
+                .iter()
+                .all(|e| matches!(e, ScanError::File { .. }))
+        );
+
+        let options = ScanOptions {
+            verify_magic: true,
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        assert_eq!(result.errors.len(), 2);
+        let misnamed: Vec<&ScanError> = result
+            .errors
+            .iter()
+            .filter(|e| matches!(e, ScanError::Misnamed { .. }))
+            .collect();
+        assert_eq!(misnamed.len(), 1);
+        assert!(
+            misnamed[0]
+                .to_string()
+                .ends_with("song.wav: named .wav but contains MP3")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_validation_report() -> anyhow::Result<()> {
+        use crate::test_support::{pcm16_spec, write_wav};
+        use Validation::*;
+
+        let dir = TempDir::new()?;
+        write_wav(&dir.path().join("good.wav"), pcm16_spec(2, 8000), 800)?;
+        write_wav(&dir.path().join("empty.wav"), pcm16_spec(2, 8000), 0)?;
+        std::fs::write(
+            dir.path().join("song.wav"),
+            b"ID3\x04\x00\x00\x00\x00\x00\x00 mp3 bytes",
+        )?;
+        std::fs::write(
+            dir.path().join("corrupt.wav"),
+            b"RIFF\x10\x00\x00\x00WAVEjunk",
+        )?;
+
+        let options = ScanOptions {
+            verify_magic: true,
+            validate: true,
+            validation_report: true,
+            ..Default::default()
+        };
+        let report = scan_directory(dir.path(), &options)
+            .validation_report
+            .unwrap();
+        assert_eq!(
+            report.checks,
+            vec![Magic, Readable, Size, Spec, ByteRate, BlockAlign]
+        );
+        let rows: Vec<(String, Vec<(Validation, bool)>)> = report
+            .rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.path.file_name().unwrap().to_string_lossy().into_owned(),
+                    row.results,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("corrupt.wav".into(), vec![(Magic, true), (Readable, false)]),
+                (
+                    "empty.wav".into(),
+                    vec![(Magic, true), (Readable, true), (Size, false)]
+                ),
+                (
+                    "good.wav".into(),
+                    vec![
+                        (Magic, true),
+                        (Readable, true),
+                        (Size, true),
+                        (Spec, true),
+                        (ByteRate, true),
+                        (BlockAlign, true)
+                    ]
+                ),
+                ("song.wav".into(), vec![(Magic, false)]),
+            ]
+        );
+
+        assert!(
+            scan_directory(dir.path(), &ScanOptions::default())
+                .validation_report
+                .is_none()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_checks_are_collected_as_warnings() -> anyhow::Result<()> {
+        use crate::header::WAVE_FORMAT_PCM;
+        use crate::header::tests::{build_header, to_rifx};
+
+        // RIFX is measured from its header, but hound can't decode it.
+        let dir = TempDir::new()?;
+        let path = dir.path().join("big_endian.wav");
+        let mut bytes = to_rifx(build_header(WAVE_FORMAT_PCM, 1, 8000, 16, None, 1600));
+        bytes.resize(bytes.len() + 1600, 0);
+        std::fs::write(&path, bytes)?;
+
+        let options = ScanOptions {
+            crest_factor_below: Some(6.0),
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        assert_eq!(result.files.len(), 1);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.check_warnings.len(), 1);
+        let warning = &result.check_warnings[0];
+        assert_eq!(
+            (warning.path.as_path(), warning.check),
+            (path.as_path(), FailedCheck::Samples)
+        );
+        assert_eq!(
+            warning.to_string(),
+            format!(
+                "Failed to check samples of {}: samples of big-endian RIFX files can't be decoded",
+                path.display()
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_flags_suspicious_specs() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        crate::test_support::write_wav(
+            &dir.path().join("fine.wav"),
+            crate::test_support::pcm16_spec(2, 8000),
+            800,
+        )?;
+        crate::test_support::write_wav(
+            &dir.path().join("wide.wav"),
+            crate::test_support::pcm16_spec(100, 8000),
+            80,
+        )?;
+        let mut odd_bits = crate::header::tests::build_header(
+            crate::header::WAVE_FORMAT_PCM,
+            1,
+            8000,
+            40,
+            None,
+            4000,
+        );
+        odd_bits.extend([0u8; 4000]);
+        std::fs::write(dir.path().join("odd_bits.wav"), odd_bits)?;
+
+        let result = scan_directory(dir.path(), &ScanOptions::default());
+        assert_eq!(result.files.len(), 1);
+        let mut suspicious: Vec<String> = result
+            .errors
+            .iter()
+            .filter(|e| matches!(e, ScanError::SuspiciousSpec { .. }))
+            .map(ToString::to_string)
+            .collect();
+        suspicious.sort();
+        assert_eq!(suspicious.len(), 2);
+        assert!(suspicious[0].ends_with(
+            "odd_bits.wav: 1 channel(s) at 40 bits per sample is implausible; the header is likely corrupt \
+             (4044 bytes, 1 ch, 8000 Hz, 40-bit)"
+        ));
+        assert!(suspicious[1].contains("wide.wav: 100 channel(s) at 16 bits"));
+
+        let options = ScanOptions {
+            max_channels: Some(128),
+            ..Default::default()
+        };
+        assert_eq!(scan_directory(dir.path(), &options).files.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_reads_raw_pcm() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        // One second of 16-bit stereo at 8 kHz with no header.
+        std::fs::write(dir.path().join("headerless.wav"), vec![0u8; 32000])?;
+        crate::test_support::write_wav(
+            &dir.path().join("real.wav"),
+            crate::test_support::pcm16_spec(1, 8000),
+            4000,
+        )?;
+
+        let result = scan_directory(dir.path(), &ScanOptions::default());
+        assert_eq!((result.files.len(), result.errors.len()), (1, 1));
+
+        let raw_format = RawFormat {
+            sample_rate: 8000,
+            channels: 2,
+            bits_per_sample: 16,
+        };
+        let options = ScanOptions {
+            raw_format: Some(raw_format),
+            verify_magic: true,
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        assert!(result.errors.is_empty());
+        let mut files = result.files;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(files[0].source, DurationSource::Raw);
+        assert_eq!(files[0].duration, Duration::from_secs(1));
+        assert_eq!(files[1].source, DurationSource::DataChunk);
+        assert_eq!(files[1].duration, Duration::from_millis(500));
+
+        // The real file's 44-byte header and mono samples are measured as
+        // stereo frames too.
+        let options = ScanOptions {
+            raw_format: Some(raw_format),
+            treat_as_raw: true,
+            ..Default::default()
+        };
+        let result = scan_directory(dir.path(), &options);
+        assert!(
+            result
+                .files
+                .iter()
+                .all(|info| info.source == DurationSource::Raw)
+        );
+        let real = result
+            .files
+            .iter()
+            .find(|info| info.path.ends_with("real.wav"))
+            .unwrap();
+        assert_eq!(real.duration, Duration::from_secs_f64(2011.0 / 8000.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_reuses_cache() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let wav_path = dir.path().join("a.wav");
+        let mut file = File::create(&wav_path)?;
+        file.write_all(include_bytes!("../test_data/minimal_wav_header.bin"))?;
+        file.write_all(&[0u8; 88200])?;
+        let cache_path = dir.path().join("cache.json");
+
+        let cache = ScanCache::open(&cache_path, false)?;
+        let first = scan_directory(
+            dir.path(),
+            &ScanOptions {
+                cache: Some(&cache),
+                ..Default::default()
+            },
+        );
+        cache.finish()?;
+        assert_eq!(first.files.len(), 1);
+
+        // Tamper with the cached duration: an unchanged file must be served
+        // from the cache rather than re-read.
+        let mut stored: serde_json::Value = serde_json::from_slice(&std::fs::read(&cache_path)?)?;
+        let key = wav_path.to_str().unwrap();
+        stored["entries"][key]["duration_nanos"] = 42u64.into();
+        std::fs::write(&cache_path, serde_json::to_vec(&stored)?)?;
+
+        let cache = ScanCache::open(&cache_path, true)?;
+        let second = scan_directory(
+            dir.path(),
+            &ScanOptions {
+                cache: Some(&cache),
+                ..Default::default()
+            },
+        );
+        assert_eq!(second.files[0].duration, Duration::from_nanos(42));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! Streams per-file records as JSON lines to a Unix domain socket while the
+//! scan is running, for live consumers such as an ingest monitor.
+
+use crate::FileRecord;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(unix)]
+use std::io::{ErrorKind, Write};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::sync::Mutex;
+
+/// A shared, thread-safe writer of JSON-lines records.
+pub struct RecordSink {
+    #[cfg(unix)]
+    stream: Mutex<Option<UnixStream>>,
+    /// The socket file this sink bound, removed again on drop.
+    #[cfg(unix)]
+    bound: Option<PathBuf>,
+    /// Stop the scan (instead of just streaming) when the consumer goes away.
+    abort_on_disconnect: bool,
+    aborted: AtomicBool,
+}
+
+impl RecordSink {
+    /// Connects to a listening consumer at `path`. If nothing exists at
+    /// `path` yet, or only a stale socket nobody listens on, binds a socket
+    /// there and waits for one consumer.
+    #[cfg(unix)]
+    pub fn connect(path: &Path, abort_on_disconnect: bool) -> anyhow::Result<Self> {
+        let (stream, bound) = match UnixStream::connect(path) {
+            Ok(stream) => (stream, None),
+            Err(e) if e.kind() == ErrorKind::NotFound || is_stale_socket(path, &e) => {
+                if e.kind() == ErrorKind::ConnectionRefused {
+                    std::fs::remove_file(path)?;
+                }
+                let listener = UnixListener::bind(path)?;
+                eprintln!("Waiting for a consumer on {}...", path.display());
+                match listener.accept() {
+                    Ok((stream, _)) => (stream, Some(path.to_path_buf())),
+                    Err(e) => {
+                        let _ = std::fs::remove_file(path);
+                        return Err(e.into());
+                    }
+                }
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(RecordSink {
+            stream: Mutex::new(Some(stream)),
+            bound,
+            abort_on_disconnect,
+            aborted: AtomicBool::new(false),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn connect(_path: &Path, _abort_on_disconnect: bool) -> anyhow::Result<Self> {
+        anyhow::bail!("--socket is only supported on Unix platforms");
+    }
+
+    /// Writes one record. A disconnected consumer is logged once; afterwards
+    /// records are dropped, or the scan is aborted if so configured.
+    #[cfg(unix)]
+    pub fn send(&self, record: &FileRecord) {
+        let mut guard = self.stream.lock().unwrap();
+        let Some(stream) = guard.as_mut() else {
+            return;
+        };
+
+        let mut line = match serde_json::to_vec(record) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Warning: failed to encode record: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        if let Err(e) = stream.write_all(&line) {
+            *guard = None;
+            if self.abort_on_disconnect {
+                eprintln!("Socket consumer disconnected ({}); aborting scan", e);
+                self.aborted.store(true, Ordering::Relaxed);
+            } else {
+                eprintln!(
+                    "Socket consumer disconnected ({}); continuing without streaming",
+                    e
+                );
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn send(&self, _record: &FileRecord) {}
+
+    /// Whether the scan should stop because the consumer disconnected.
+    pub fn aborted(&self) -> bool {
+        self.aborted.load(Ordering::Relaxed)
+    }
+}
+
+/// Whether connecting to `path` failed because it is a socket left behind
+/// by a process that no longer listens on it. Other files are never stale.
+#[cfg(unix)]
+fn is_stale_socket(path: &Path, error: &std::io::Error) -> bool {
+    error.kind() == ErrorKind::ConnectionRefused
+        && std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket())
+}
+
+#[cfg(unix)]
+impl Drop for RecordSink {
+    fn drop(&mut self) {
+        if let Some(path) = &self.bound {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{DurationSource, WavInfo};
+    use std::io::{BufRead, BufReader};
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn record(name: &str) -> FileRecord {
+        let info = WavInfo {
+            path: PathBuf::from(name),
+            duration: Duration::from_millis(1500),
+            spec: hound::WavSpec {
+                channels: 2,
+                sample_rate: 48000,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            },
+            source: DurationSource::DataChunk,
+            file_size: None,
+            data_size: None,
+            modified: None,
+        };
+        FileRecord::new(&PathBuf::from(name), Ok(&info))
+    }
+
+    #[test]
+    fn test_send_writes_json_lines() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let socket_path = dir.path().join("records.sock");
+        let listener = UnixListener::bind(&socket_path)?;
+
+        let sink = RecordSink::connect(&socket_path, false)?;
+        let (consumer, _) = listener.accept()?;
+        sink.send(&record("a.wav"));
+        sink.send(&record("b.wav"));
+        drop(sink);
+
+        let lines: Vec<serde_json::Value> = BufReader::new(consumer)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["path"], "a.wav");
+        assert_eq!(lines[1]["duration_secs"], 1.5);
+        assert_eq!(lines[1]["channels"], 2);
+
+        Ok(())
+    }
+
+    /// Connects to `path` as soon as something listens there.
+    fn spawn_consumer(path: PathBuf) -> std::thread::JoinHandle<Vec<String>> {
+        std::thread::spawn(move || {
+            let stream = loop {
+                match UnixStream::connect(&path) {
+                    Ok(stream) => break stream,
+                    Err(_) => std::thread::sleep(Duration::from_millis(10)),
+                }
+            };
+            BufReader::new(stream).lines().map(Result::unwrap).collect()
+        })
+    }
+
+    #[test]
+    fn test_bound_socket_is_removed_and_reusable() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let socket_path = dir.path().join("records.sock");
+
+        for name in ["a.wav", "b.wav"] {
+            let consumer = spawn_consumer(socket_path.clone());
+            let sink = RecordSink::connect(&socket_path, false)?;
+            sink.send(&record(name));
+            drop(sink);
+            assert!(!socket_path.exists());
+            let lines = consumer.join().unwrap();
+            assert_eq!(lines.len(), 1);
+            assert!(lines[0].contains(name));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_socket_is_replaced() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let socket_path = dir.path().join("records.sock");
+        // A listener that went away without removing its socket file.
+        drop(UnixListener::bind(&socket_path)?);
+        assert!(socket_path.exists());
+
+        let consumer = spawn_consumer(socket_path.clone());
+        let sink = RecordSink::connect(&socket_path, false)?;
+        sink.send(&record("a.wav"));
+        drop(sink);
+        assert_eq!(consumer.join().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_regular_file_is_not_replaced() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("records.sock");
+        std::fs::write(&path, "not a socket")?;
+
+        assert!(RecordSink::connect(&path, false).is_err());
+        assert_eq!(std::fs::read_to_string(&path)?, "not a socket");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_survives_disconnect() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let socket_path = dir.path().join("records.sock");
+        let listener = UnixListener::bind(&socket_path)?;
+
+        let sink = RecordSink::connect(&socket_path, true)?;
+        drop(listener.accept()?);
+        // The first writes may still land in the kernel buffer; keep sending
+        // until the broken pipe surfaces.
+        for _ in 0..1000 {
+            sink.send(&record("a.wav"));
+            if sink.aborted() {
+                break;
+            }
+        }
+        assert!(sink.aborted());
+
+        Ok(())
+    }
+}
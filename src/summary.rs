@@ -0,0 +1,2455 @@
+//! Aggregate statistics over the files of one scan.
+
+use crate::resample::{ResamplePlan, TargetFormat};
+use crate::samples::{CrestFactorCheck, DecodeCheck, FloatSampleCheck, NoiseFloorCheck};
+use crate::scan::{SampleStats, is_gzipped_wav};
+use crate::validate::{ByteRateCheck, StrictWavCheck};
+use crate::{DurationSource, ScanError, ScanResult, WavInfo};
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// (De)serializes a `Duration` as fractional seconds.
+pub(crate) mod secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+    }
+}
+
+/// (De)serializes a path as a string, converted lossily if it is not valid
+/// UTF-8, so the path itself stays exact until it is written out.
+pub(crate) mod lossy_path {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::path::{Path, PathBuf};
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&path.to_string_lossy())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        String::deserialize(deserializer).map(PathBuf::from)
+    }
+}
+
+/// (De)serializes an optional `Duration` as fractional seconds.
+pub(crate) mod opt_secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => super::secs::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<f64>::deserialize(deserializer)?
+            .map(|secs| Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// (De)serializes a `SystemTime` as an ISO-8601 (RFC 3339) UTC timestamp.
+pub(crate) mod rfc3339 {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&humantime::format_rfc3339_seconds(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        humantime::parse_rfc3339_weak(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+/// One bucket of the duration histogram, covering `[range_start, range_end)`
+/// in seconds. The last bucket also includes its upper edge.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    pub range_start: f64,
+    pub range_end: f64,
+    pub count: usize,
+    pub total_seconds: f64,
+}
+
+/// Splits the span between the shortest and longest duration into
+/// `bucket_count` equal-width buckets.
+pub fn build_histogram(durations: &[Duration], bucket_count: usize) -> Vec<HistogramBucket> {
+    if durations.is_empty() || bucket_count == 0 {
+        return Vec::new();
+    }
+
+    let secs: Vec<f64> = durations.iter().map(Duration::as_secs_f64).collect();
+    let min = secs.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = secs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    // All files share one duration: a single bucket holds everything.
+    let bucket_count = if max > min { bucket_count } else { 1 };
+    let width = (max - min) / bucket_count as f64;
+
+    let mut buckets: Vec<HistogramBucket> = (0..bucket_count)
+        .map(|i| HistogramBucket {
+            range_start: min + width * i as f64,
+            range_end: if i + 1 == bucket_count {
+                max
+            } else {
+                min + width * (i + 1) as f64
+            },
+            count: 0,
+            total_seconds: 0.0,
+        })
+        .collect();
+
+    for value in secs {
+        let index = if width > 0.0 {
+            (((value - min) / width) as usize).min(bucket_count - 1)
+        } else {
+            0
+        };
+        buckets[index].count += 1;
+        buckets[index].total_seconds += value;
+    }
+
+    buckets
+}
+
+/// Count and total duration of the files sharing one key, e.g. a bit depth.
+/// The total is kept as a [`FrameTotal`], so group totals add up to the
+/// overall one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DurationGroup {
+    pub count: usize,
+    total: FrameTotal,
+}
+
+impl DurationGroup {
+    pub fn add(&mut self, duration: Duration, sample_rate: u32) {
+        self.count += 1;
+        self.total.add(duration, sample_rate, 1);
+    }
+
+    /// A group known only by its count and rounded total, e.g. from a
+    /// report being merged.
+    pub fn reported(count: usize, total: Duration) -> Self {
+        let mut group = DurationGroup {
+            count,
+            ..Default::default()
+        };
+        group.total.add_reported(total);
+        group
+    }
+
+    pub fn merge(&mut self, other: DurationGroup) {
+        self.count += other.count;
+        self.total.merge(other.total);
+    }
+
+    pub fn total(&self) -> Duration {
+        self.total.duration()
+    }
+
+    pub fn average(&self) -> Duration {
+        mean_duration(self.total(), self.count)
+    }
+}
+
+/// Duration aggregates for all files with one bit depth.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BitDepthStats {
+    pub bits_per_sample: u16,
+    pub file_count: usize,
+    #[serde(rename = "total_duration_secs", with = "secs")]
+    pub total_duration: Duration,
+    #[serde(rename = "average_duration_secs", with = "secs")]
+    pub average_duration: Duration,
+}
+
+/// Duration aggregates for all files with one sample rate, for planning
+/// per-rate transcode jobs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleRateStats {
+    pub sample_rate: u32,
+    pub file_count: usize,
+    #[serde(rename = "total_duration_secs", with = "secs")]
+    pub total_duration: Duration,
+}
+
+/// Duration aggregates for the files whose name gave one `group_pattern` key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NameGroupStats {
+    /// The captured text, or [`UNMATCHED_GROUP`].
+    pub key: String,
+    pub file_count: usize,
+    #[serde(rename = "total_duration_secs", with = "secs")]
+    pub total_duration: Duration,
+    #[serde(rename = "average_duration_secs", with = "secs")]
+    pub average_duration: Duration,
+}
+
+/// Totals that count each channel of a file as a mono file of the same
+/// duration (`per_channel`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PerChannelStats {
+    /// Files on disk.
+    pub physical_file_count: usize,
+    /// Channels across all files, each a virtual mono file.
+    pub file_count: usize,
+    #[serde(rename = "total_duration_secs", with = "secs")]
+    pub total_duration: Duration,
+    #[serde(rename = "average_duration_secs", with = "secs")]
+    pub average_duration: Duration,
+}
+
+impl PerChannelStats {
+    fn new(physical_file_count: usize, channels: DurationGroup) -> Self {
+        PerChannelStats {
+            physical_file_count,
+            file_count: channels.count,
+            total_duration: channels.total(),
+            average_duration: channels.average(),
+        }
+    }
+}
+
+/// The group of files whose name doesn't match `group_pattern`.
+pub const UNMATCHED_GROUP: &str = "(unmatched)";
+
+/// The `group_pattern` key of `path`: the first capture group matched in
+/// its file name.
+fn name_group_key(pattern: &Regex, path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    pattern
+        .captures(&name)
+        .and_then(|captures| captures.get(1))
+        .map_or_else(
+            || UNMATCHED_GROUP.to_string(),
+            |key| key.as_str().to_string(),
+        )
+}
+
+/// Turns per-key groups into stats, sorted by key.
+fn name_group_stats(groups: BTreeMap<String, DurationGroup>) -> Vec<NameGroupStats> {
+    groups
+        .into_iter()
+        .map(|(key, group)| NameGroupStats {
+            key,
+            file_count: group.count,
+            total_duration: group.total(),
+            average_duration: group.average(),
+        })
+        .collect()
+}
+
+/// Turns per-rate groups into stats, longest total duration first.
+fn sample_rate_stats(groups: BTreeMap<u32, DurationGroup>) -> Vec<SampleRateStats> {
+    let mut stats: Vec<SampleRateStats> = groups
+        .into_iter()
+        .map(|(sample_rate, group)| SampleRateStats {
+            sample_rate,
+            file_count: group.count,
+            total_duration: group.total(),
+        })
+        .collect();
+    // Stable, so equal totals stay in ascending rate order.
+    stats.sort_by_key(|stat| Reverse(stat.total_duration));
+    stats
+}
+
+/// The sample rate shared by the most files; ties go to the larger total
+/// duration, then to the higher rate.
+fn most_common_sample_rate(groups: &BTreeMap<u32, DurationGroup>) -> Option<SampleRateStats> {
+    groups
+        .iter()
+        .max_by_key(|&(rate, group)| (group.count, group.total(), *rate))
+        .map(|(&sample_rate, group)| SampleRateStats {
+            sample_rate,
+            file_count: group.count,
+            total_duration: group.total(),
+        })
+}
+
+/// Sample rates that `--warn-nonstandard-rate` accepts without extension.
+pub const STANDARD_SAMPLE_RATES: &[u32] = &[8000, 16000, 22050, 44100, 48000, 88200, 96000, 192000];
+
+/// A file whose sample rate is outside the allowed set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NonstandardRate {
+    /// The file path, converted lossily if it is not valid UTF-8.
+    pub path: String,
+    pub sample_rate: u32,
+}
+
+/// Marks a summary built from a sampled scan, whose statistics cover only
+/// the sample, and extrapolates the total to every matched file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleEstimate {
+    pub seed: u64,
+    /// `.wav` files the sample was drawn from.
+    pub matched_files: usize,
+    /// Files in the sample, including those that failed to read.
+    pub sampled_files: usize,
+    /// Sampled files that failed to read. They have no duration, so they
+    /// are left out of the scale rather than counted as silent.
+    #[serde(default)]
+    pub unreadable_files: usize,
+    /// The sample's total duration scaled by `matched / read`, where
+    /// `read` is the sampled files that read successfully.
+    #[serde(rename = "estimated_total_duration_secs", with = "secs")]
+    pub estimated_total_duration: Duration,
+}
+
+impl SampleEstimate {
+    /// Extrapolates `total`, the duration of the `files_read` sampled files
+    /// that read successfully, to every match.
+    pub fn new(stats: SampleStats, total: Duration, files_read: usize) -> Self {
+        let scale = if files_read == 0 {
+            0.0
+        } else {
+            stats.matched as f64 / files_read as f64
+        };
+        SampleEstimate {
+            seed: stats.seed,
+            matched_files: stats.matched,
+            sampled_files: stats.sampled,
+            unreadable_files: stats.sampled.saturating_sub(files_read),
+            estimated_total_duration: total.mul_f64(scale),
+        }
+    }
+}
+
+/// A file and its size on disk, for the smallest and largest file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizedFile {
+    pub bytes: u64,
+    /// The file path, converted lossily if it is not valid UTF-8.
+    pub path: String,
+}
+
+fn duration_from_nanos(nanos: u128) -> Duration {
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
+
+/// `total / count` rounded to the nanosecond, or zero for no files.
+///
+/// Dividing the `Duration` itself would need the count as a `u32` and
+/// truncate; this divides the total nanoseconds in `u128` instead.
+fn mean_duration(total: Duration, count: usize) -> Duration {
+    if count == 0 {
+        return Duration::ZERO;
+    }
+    let count = count as u128;
+    duration_from_nanos((total.as_nanos() + count / 2) / count)
+}
+
+/// The median of `sorted`, or `None` if it is empty.
+fn median(sorted: &[Duration]) -> Option<Duration> {
+    let middle = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        len if len % 2 == 1 => Some(sorted[middle]),
+        _ => Some((sorted[middle - 1] + sorted[middle]) / 2),
+    }
+}
+
+/// An exact sum of durations, kept as frame counts per sample rate.
+///
+/// Adding up each file's rounded `Duration` carries its rounding error into
+/// the total; the frame counts don't, so the total is only rounded once per
+/// distinct rate when it is read.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct FrameTotal {
+    frames_by_rate: BTreeMap<u32, u128>,
+}
+
+/// The rate that counts a [`Duration`] exactly, as nanoseconds.
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+impl FrameTotal {
+    /// Adds `duration` at `sample_rate`, `times` over, e.g. once per channel.
+    fn add(&mut self, duration: Duration, sample_rate: u32, times: u32) {
+        *self.frames_by_rate.entry(sample_rate).or_default() +=
+            crate::frame_count(duration, sample_rate) * u128::from(times);
+    }
+
+    /// Adds a total whose frames aren't known, e.g. from a merged report.
+    fn add_reported(&mut self, total: Duration) {
+        self.add(total, NANOS_PER_SEC, 1);
+    }
+
+    fn merge(&mut self, other: FrameTotal) {
+        for (rate, frames) in other.frames_by_rate {
+            *self.frames_by_rate.entry(rate).or_default() += frames;
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.frames_by_rate
+            .iter()
+            .filter(|(rate, _)| **rate > 0)
+            .map(|(&rate, &frames)| {
+                let rate = u128::from(rate);
+                duration_from_nanos((frames * 1_000_000_000 + rate / 2) / rate)
+            })
+            .sum()
+    }
+}
+
+/// The files holding the smallest and largest value of some per-file
+/// statistic. Ties go to the first path, so the pick doesn't depend on the
+/// order partial aggregates are merged in.
+///
+/// Paths are `String`s, or `OsString`s where the file has to be found again;
+/// both order byte-wise, so ties break the same way.
+#[derive(Debug, Clone)]
+struct Extremes<T, P = String> {
+    min: Option<(T, P)>,
+    max: Option<(T, P)>,
+}
+
+impl<T, P> Default for Extremes<T, P> {
+    fn default() -> Self {
+        Extremes {
+            min: None,
+            max: None,
+        }
+    }
+}
+
+impl<T: Ord + Copy, P: Ord + Clone> Extremes<T, P> {
+    /// Considers one file; `path` is only copied if the file is picked.
+    fn offer<Q>(&mut self, value: T, path: &Q)
+    where
+        Q: Ord + ToOwned<Owned = P> + ?Sized,
+        P: Borrow<Q>,
+    {
+        let beats = |current: &Option<(T, P)>, wanted: std::cmp::Ordering| match current {
+            None => true,
+            Some((best, best_path)) => match value.cmp(best) {
+                std::cmp::Ordering::Equal => path < best_path.borrow(),
+                ordering => ordering == wanted,
+            },
+        };
+        if beats(&self.min, std::cmp::Ordering::Less) {
+            self.min = Some((value, path.to_owned()));
+        }
+        if beats(&self.max, std::cmp::Ordering::Greater) {
+            self.max = Some((value, path.to_owned()));
+        }
+    }
+
+    fn merge(&mut self, other: Extremes<T, P>) {
+        for (value, path) in other.min.into_iter().chain(other.max) {
+            self.offer::<P>(value, &path);
+        }
+    }
+}
+
+/// One of the longest files (`top`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RankedFile {
+    /// The file path, converted lossily if it is not valid UTF-8.
+    pub path: String,
+    #[serde(rename = "duration_secs", with = "secs")]
+    pub duration: Duration,
+}
+
+/// The longest files of a summary (`top`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LongestFiles {
+    /// How many files were asked for; `files` is shorter only if there
+    /// weren't as many files.
+    pub limit: usize,
+    /// Longest first, equal durations by path.
+    pub files: Vec<RankedFile>,
+}
+
+/// Orders files by duration, and equal durations so the smaller path ranks
+/// higher, matching how [`Extremes`] breaks ties.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Rank {
+    duration: Duration,
+    path: Reverse<String>,
+}
+
+/// The `n` longest files offered so far. They sit in a min-heap with the
+/// shortest kept file on top, so each offer costs `O(log n)` and nothing
+/// but those `n` files is kept, however many are offered.
+#[derive(Debug, Clone, Default)]
+struct TopFiles {
+    n: usize,
+    heap: BinaryHeap<Reverse<Rank>>,
+}
+
+impl TopFiles {
+    fn new(n: usize) -> Self {
+        TopFiles {
+            n,
+            // `n` is user input, so the heap only grows with the files kept.
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    /// Considers one file; `path` is only copied if the file is kept.
+    fn offer(&mut self, duration: Duration, path: &str) {
+        if self.heap.len() == self.n {
+            let Some(Reverse(shortest)) = self.heap.peek() else {
+                return;
+            };
+            let beats = match duration.cmp(&shortest.duration) {
+                std::cmp::Ordering::Equal => path < shortest.path.0.as_str(),
+                ordering => ordering == std::cmp::Ordering::Greater,
+            };
+            if !beats {
+                return;
+            }
+            self.heap.pop();
+        }
+        self.heap.push(Reverse(Rank {
+            duration,
+            path: Reverse(path.to_string()),
+        }));
+    }
+
+    fn merge(&mut self, other: TopFiles) {
+        for Reverse(rank) in other.heap {
+            self.offer(rank.duration, &rank.path.0);
+        }
+    }
+
+    fn into_longest_files(self) -> LongestFiles {
+        LongestFiles {
+            limit: self.n,
+            // Ascending `Reverse` order is descending rank.
+            files: self
+                .heap
+                .into_sorted_vec()
+                .into_iter()
+                .map(|Reverse(rank)| RankedFile {
+                    path: rank.path.0,
+                    duration: rank.duration,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A file and its modification time, for the oldest and newest file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatedFile {
+    #[serde(with = "rfc3339")]
+    pub modified: SystemTime,
+    /// The file path, converted lossily if it is not valid UTF-8.
+    pub path: String,
+}
+
+/// The fraction of attempted files that failed, or `None` without any.
+pub fn error_rate(file_count: usize, error_count: usize) -> Option<f64> {
+    let attempted = file_count + error_count;
+    (attempted > 0).then(|| error_count as f64 / attempted as f64)
+}
+
+/// Days between the oldest and newest file's modification times.
+pub fn time_span_days(oldest: &DatedFile, newest: &DatedFile) -> f64 {
+    newest
+        .modified
+        .duration_since(oldest.modified)
+        .unwrap_or_default()
+        .as_secs_f64()
+        / 86_400.0
+}
+
+/// Turns the extremes of modification times into the reported oldest and newest file.
+fn dated_files(extremes: Extremes<SystemTime>) -> (Option<DatedFile>, Option<DatedFile>) {
+    let to_file = |(modified, path)| DatedFile { modified, path };
+    (extremes.min.map(to_file), extremes.max.map(to_file))
+}
+
+/// Turns the extremes of file sizes into the reported smallest and largest file.
+fn sized_files(extremes: Extremes<u64>) -> (Option<SizedFile>, Option<SizedFile>) {
+    let to_file = |(bytes, path)| SizedFile { bytes, path };
+    (extremes.min.map(to_file), extremes.max.map(to_file))
+}
+
+/// Whether `rate` is neither standard nor in `extra_allowed`.
+pub fn is_nonstandard_rate(rate: u32, extra_allowed: &[u32]) -> bool {
+    !STANDARD_SAMPLE_RATES.contains(&rate) && !extra_allowed.contains(&rate)
+}
+
+/// A format property that `--require-uniform` can demand be the same for
+/// every file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormatDimension {
+    /// Sample rate in Hz.
+    Rate,
+    /// Number of channels.
+    Channels,
+    /// Bits per sample.
+    Bits,
+}
+
+impl FormatDimension {
+    pub fn value_of(self, info: &WavInfo) -> u32 {
+        match self {
+            FormatDimension::Rate => info.spec.sample_rate,
+            FormatDimension::Channels => info.spec.channels as u32,
+            FormatDimension::Bits => info.spec.bits_per_sample as u32,
+        }
+    }
+}
+
+impl std::fmt::Display for FormatDimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatDimension::Rate => write!(f, "sample rate"),
+            FormatDimension::Channels => write!(f, "channel count"),
+            FormatDimension::Bits => write!(f, "bit depth"),
+        }
+    }
+}
+
+/// How many example paths are kept per distinct value of a dimension.
+const UNIFORMITY_EXAMPLES: usize = 3;
+
+/// The files sharing one value of a checked dimension.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DimensionValue {
+    pub value: u32,
+    pub file_count: usize,
+    /// The first few paths (in sorted order) with this value.
+    pub examples: Vec<String>,
+}
+
+impl DimensionValue {
+    fn add_example(&mut self, path: String) {
+        let at = self.examples.binary_search(&path).unwrap_or_else(|at| at);
+        if at < UNIFORMITY_EXAMPLES {
+            self.examples.insert(at, path);
+            self.examples.truncate(UNIFORMITY_EXAMPLES);
+        }
+    }
+}
+
+/// The distinct values of one dimension that must be uniform.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UniformityCheck {
+    pub dimension: FormatDimension,
+    pub values: Vec<DimensionValue>,
+}
+
+impl UniformityCheck {
+    pub fn passed(&self) -> bool {
+        self.values.len() <= 1
+    }
+}
+
+/// Whether every file is at the sample rate a consumer requires
+/// (`required_rate`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateVerdict {
+    pub sample_rate: u32,
+    pub file_count: usize,
+    /// The files at another rate, sorted by path.
+    pub mismatched: Vec<NonstandardRate>,
+}
+
+impl RateVerdict {
+    pub fn new(sample_rate: u32, file_count: usize, mut mismatched: Vec<NonstandardRate>) -> Self {
+        mismatched.sort_by(|a, b| a.path.cmp(&b.path));
+        RateVerdict {
+            sample_rate,
+            file_count,
+            mismatched,
+        }
+    }
+
+    pub fn compatible(&self) -> bool {
+        self.mismatched.is_empty()
+    }
+}
+
+/// Optional parts of the summary, off unless requested.
+#[derive(Debug, Default, Clone)]
+pub struct SummaryOptions {
+    /// Build a duration histogram with this many buckets.
+    pub histogram_buckets: Option<usize>,
+    /// List files with non-standard sample rates, additionally accepting
+    /// these rates.
+    pub allowed_rates: Option<Vec<u32>>,
+    /// Judge whether every file is at this sample rate.
+    pub required_rate: Option<u32>,
+    /// Compute the geometric mean of the durations.
+    pub geometric_mean: bool,
+    /// Compute the median duration, which keeps every duration in memory.
+    pub median: bool,
+    /// Report total duration per sample rate.
+    pub plan_transcode: bool,
+    /// Dimensions that every file must share a value in.
+    pub require_uniform: Vec<FormatDimension>,
+    /// Group files by the first capture group of this pattern in their
+    /// file name.
+    pub group_pattern: Option<Regex>,
+    /// Report totals with every channel counted as a separate mono file.
+    pub per_channel: bool,
+    /// Estimate the corpus size after conversion to this format.
+    pub resample_target: Option<TargetFormat>,
+    /// List this many of the longest files.
+    pub top: Option<usize>,
+}
+
+/// Aggregate statistics for one scan, shared by every output format.
+///
+/// Fields missing from a deserialized report (e.g. one written by an older
+/// version) take their default.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Summary {
+    pub file_count: usize,
+    #[serde(rename = "total_duration_secs", with = "secs")]
+    pub total_duration: Duration,
+    #[serde(rename = "average_duration_secs", with = "secs")]
+    pub average_duration: Duration,
+    /// `exp(mean(ln(secs)))` over the files with a non-zero duration.
+    #[serde(
+        rename = "geometric_mean_secs",
+        with = "opt_secs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub geometric_mean: Option<Duration>,
+    /// The middle duration, or the mean of the two middle ones (`median`).
+    /// Merged reports don't have it.
+    #[serde(
+        rename = "median_duration_secs",
+        with = "opt_secs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub median_duration: Option<Duration>,
+    #[serde(rename = "shortest_secs", with = "secs")]
+    pub min_duration: Duration,
+    #[serde(rename = "longest_secs", with = "secs")]
+    pub max_duration: Duration,
+    /// Paths of the files with `min_duration` and `max_duration`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shortest_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longest_path: Option<String>,
+    /// The same two files as found on disk, for reading them again. Not
+    /// part of a report, so merged reports don't have them.
+    #[serde(skip)]
+    pub shortest_disk_path: Option<PathBuf>,
+    #[serde(skip)]
+    pub longest_disk_path: Option<PathBuf>,
+    /// Sum of `duration * channels`, the sample-seconds.
+    pub total_channel_seconds: f64,
+    /// Files with more than one channel; with none, `total_channel_seconds`
+    /// is just the total duration.
+    pub multichannel_files: usize,
+    pub total_audio_bytes: u64,
+    pub total_overhead_bytes: u64,
+    /// Size on disk of all files whose metadata could be read.
+    pub total_file_bytes: u64,
+    /// Average size of the files whose metadata could be read.
+    pub average_file_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smallest_file: Option<SizedFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub largest_file: Option<SizedFile>,
+    /// The files with the earliest and latest modification time, among
+    /// those whose time could be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oldest_file: Option<DatedFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newest_file: Option<DatedFile>,
+    pub distinct_sample_rates: usize,
+    /// The mode of the sample rate distribution. After a merge it is only
+    /// known if the reports carried `sample_rates`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub most_common_sample_rate: Option<SampleRateStats>,
+    pub distinct_channel_layouts: usize,
+    pub distinct_bit_depths: usize,
+    /// Files in the 64-bit RF64/BW64 container.
+    pub rf64_files: usize,
+    /// Big-endian RIFX files.
+    pub rifx_files: usize,
+    /// Gzipped `.wav.gz` files.
+    pub gzipped_files: usize,
+    /// Files measured as headerless PCM in an assumed format.
+    pub raw_files: usize,
+    pub error_count: usize,
+    /// `error_count / (file_count + error_count)`, as a fraction; `None`
+    /// when there was nothing to read.
+    pub error_rate: Option<f64>,
+    /// Errors that are `.wav` files holding another format (`verify_magic`).
+    pub misnamed_count: usize,
+    /// Errors that are files too short for one complete frame.
+    pub sub_frame_count: usize,
+    /// Errors that are files with an implausible channel count or bit depth.
+    pub suspicious_spec_count: usize,
+    pub directories_scanned: usize,
+    pub cyclic_symlinks_pruned: usize,
+    pub hardlink_duplicates: usize,
+    /// Files left out because they were modified too recently (`skip_recent`).
+    pub recently_modified_skipped: usize,
+    /// Files counted without a size or date because their metadata couldn't
+    /// be read.
+    pub metadata_unavailable: usize,
+    pub bit_depths: Vec<BitDepthStats>,
+    /// Per file name group totals, sorted by key (`group_pattern`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_groups: Option<Vec<NameGroupStats>>,
+    /// Totals over channels rather than files (`per_channel`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_channel: Option<PerChannelStats>,
+    /// The longest files, longest first (`top`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longest_files: Option<LongestFiles>,
+    /// Sizes before and after conversion (`resample_target`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resample_plan: Option<ResamplePlan>,
+    /// Per sample rate totals, longest first (`plan_transcode`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rates: Option<Vec<SampleRateStats>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub histogram: Option<Vec<HistogramBucket>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonstandard_rates: Option<Vec<NonstandardRate>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_verdict: Option<RateVerdict>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub uniformity: Vec<UniformityCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub float_sample_check: Option<FloatSampleCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crest_factor: Option<CrestFactorCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noise_floor: Option<NoiseFloorCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub byte_rate_check: Option<ByteRateCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict_wav_check: Option<StrictWavCheck>,
+    /// Set if durations were measured by decoding every file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decode_check: Option<DecodeCheck>,
+    /// Set if only a sample of the matched files was read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample: Option<SampleEstimate>,
+}
+
+/// Running totals that a [`Summary`] is built from.
+///
+/// Files are added one at a time and the partial aggregates of parallel
+/// workers are merged, so memory is bounded by the number of distinct
+/// formats rather than the number of files. Only the extras that need
+/// per-file detail (the histogram, the rate check) keep anything per file.
+#[derive(Debug, Default, Clone)]
+pub struct Aggregate {
+    options: SummaryOptions,
+    file_count: usize,
+    total_duration: FrameTotal,
+    /// Keyed by the path as found on disk, so the files can be reopened.
+    durations_by_file: Extremes<Duration, OsString>,
+    /// Sum of `duration * channels`, kept exact until it is reported.
+    channel_duration: FrameTotal,
+    /// Sum of the channel counts.
+    channel_count: usize,
+    multichannel_files: usize,
+    total_audio_bytes: u64,
+    total_overhead_bytes: u64,
+    total_file_bytes: u64,
+    /// Files whose size is known, and the extremes among them.
+    sized_file_count: usize,
+    sizes_by_file: Extremes<u64>,
+    modified_by_file: Extremes<SystemTime>,
+    rf64_files: usize,
+    rifx_files: usize,
+    gzipped_files: usize,
+    raw_files: usize,
+    sample_rates: BTreeMap<u32, DurationGroup>,
+    channel_layouts: BTreeSet<u16>,
+    bit_depths: BTreeMap<u16, DurationGroup>,
+    /// Sum of `ln(secs)` and the number of non-zero durations in it.
+    log_duration_sum: f64,
+    nonzero_count: usize,
+    /// Every duration, kept only when a histogram or the median was
+    /// requested.
+    durations: Vec<Duration>,
+    nonstandard_rates: Vec<NonstandardRate>,
+    /// Files not at `required_rate`.
+    off_required_rate: Vec<NonstandardRate>,
+    /// Per value counts and examples of each `require_uniform` dimension.
+    uniformity: BTreeMap<FormatDimension, BTreeMap<u32, DimensionValue>>,
+    /// Per key totals, kept only when a `group_pattern` was given.
+    name_groups: BTreeMap<String, DurationGroup>,
+    resample_plan: Option<ResamplePlan>,
+    /// The longest files, kept only when `top` was given.
+    longest_files: Option<TopFiles>,
+}
+
+impl Aggregate {
+    pub fn new(options: &SummaryOptions) -> Self {
+        Aggregate {
+            options: options.clone(),
+            resample_plan: options.resample_target.map(ResamplePlan::new),
+            longest_files: options.top.map(TopFiles::new),
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, info: &WavInfo) {
+        self.file_count += 1;
+        self.total_duration
+            .add(info.duration, info.spec.sample_rate, 1);
+        self.durations_by_file
+            .offer(info.duration, info.path.as_os_str());
+        let path = info.path.to_string_lossy();
+        if let Some(top) = &mut self.longest_files {
+            top.offer(info.duration, &path);
+        }
+        self.channel_duration.add(
+            info.duration,
+            info.spec.sample_rate,
+            u32::from(info.spec.channels),
+        );
+        self.channel_count += info.spec.channels as usize;
+        if info.spec.channels > 1 {
+            self.multichannel_files += 1;
+        }
+        if let Some(plan) = &mut self.resample_plan {
+            plan.add(info);
+        }
+        self.total_audio_bytes += info.data_size.unwrap_or(0);
+        if let Some(bytes) = info.file_size {
+            self.total_file_bytes += bytes;
+            self.sized_file_count += 1;
+            self.sizes_by_file.offer(bytes, &*path);
+        }
+        if let Some(modified) = info.modified {
+            self.modified_by_file.offer(modified, &*path);
+        }
+        // Overhead is only known when both the file and data chunk sizes
+        // are; a data chunk declaring more than the file holds counts as 0.
+        if let (Some(file_size), Some(data_size)) = (info.file_size, info.data_size) {
+            self.total_overhead_bytes += file_size.saturating_sub(data_size);
+        }
+        match info.source {
+            DurationSource::Ds64Chunk => self.rf64_files += 1,
+            DurationSource::Rifx => self.rifx_files += 1,
+            DurationSource::Raw => self.raw_files += 1,
+            DurationSource::DataChunk | DurationSource::FactChunk => {}
+        }
+        if is_gzipped_wav(&info.path) {
+            self.gzipped_files += 1;
+        }
+        self.sample_rates
+            .entry(info.spec.sample_rate)
+            .or_default()
+            .add(info.duration, info.spec.sample_rate);
+        self.channel_layouts.insert(info.spec.channels);
+        self.bit_depths
+            .entry(info.spec.bits_per_sample)
+            .or_default()
+            .add(info.duration, info.spec.sample_rate);
+        // The logarithm of zero is undefined, so empty durations are left
+        // out of the geometric mean rather than pulling it to zero.
+        if !info.duration.is_zero() {
+            self.log_duration_sum += info.duration.as_secs_f64().ln();
+            self.nonzero_count += 1;
+        }
+        if self.options.histogram_buckets.is_some() || self.options.median {
+            self.durations.push(info.duration);
+        }
+        if let Some(allowed) = &self.options.allowed_rates
+            && is_nonstandard_rate(info.spec.sample_rate, allowed)
+        {
+            self.nonstandard_rates.push(NonstandardRate {
+                path: info.path.to_string_lossy().into_owned(),
+                sample_rate: info.spec.sample_rate,
+            });
+        }
+        if let Some(required) = self.options.required_rate
+            && info.spec.sample_rate != required
+        {
+            self.off_required_rate.push(NonstandardRate {
+                path: info.path.to_string_lossy().into_owned(),
+                sample_rate: info.spec.sample_rate,
+            });
+        }
+        for &dimension in &self.options.require_uniform {
+            let value = dimension.value_of(info);
+            let entry = self
+                .uniformity
+                .entry(dimension)
+                .or_default()
+                .entry(value)
+                .or_insert_with(|| DimensionValue {
+                    value,
+                    file_count: 0,
+                    examples: Vec::new(),
+                });
+            entry.file_count += 1;
+            entry.add_example(info.path.to_string_lossy().into_owned());
+        }
+        if let Some(pattern) = &self.options.group_pattern {
+            self.name_groups
+                .entry(name_group_key(pattern, &info.path))
+                .or_default()
+                .add(info.duration, info.spec.sample_rate);
+        }
+    }
+
+    /// Combines the aggregates of two disjoint sets of files.
+    pub fn merge(mut self, other: Aggregate) -> Self {
+        self.file_count += other.file_count;
+        self.total_duration.merge(other.total_duration);
+        self.durations_by_file.merge(other.durations_by_file);
+        if let (Some(top), Some(other_top)) = (&mut self.longest_files, other.longest_files) {
+            top.merge(other_top);
+        }
+        self.channel_duration.merge(other.channel_duration);
+        self.channel_count += other.channel_count;
+        self.multichannel_files += other.multichannel_files;
+        if let (Some(plan), Some(other_plan)) = (&mut self.resample_plan, &other.resample_plan) {
+            plan.merge(other_plan);
+        }
+        self.total_audio_bytes += other.total_audio_bytes;
+        self.total_overhead_bytes += other.total_overhead_bytes;
+        self.total_file_bytes += other.total_file_bytes;
+        self.sized_file_count += other.sized_file_count;
+        self.sizes_by_file.merge(other.sizes_by_file);
+        self.modified_by_file.merge(other.modified_by_file);
+        self.rf64_files += other.rf64_files;
+        self.rifx_files += other.rifx_files;
+        self.gzipped_files += other.gzipped_files;
+        self.raw_files += other.raw_files;
+        for (rate, group) in other.sample_rates {
+            self.sample_rates.entry(rate).or_default().merge(group);
+        }
+        self.channel_layouts.extend(other.channel_layouts);
+        for (bits, group) in other.bit_depths {
+            self.bit_depths.entry(bits).or_default().merge(group);
+        }
+        self.log_duration_sum += other.log_duration_sum;
+        self.nonzero_count += other.nonzero_count;
+        self.durations.extend(other.durations);
+        self.nonstandard_rates.extend(other.nonstandard_rates);
+        self.off_required_rate.extend(other.off_required_rate);
+        for (dimension, values) in other.uniformity {
+            let merged = self.uniformity.entry(dimension).or_default();
+            for (value, other) in values {
+                match merged.get_mut(&value) {
+                    Some(entry) => {
+                        entry.file_count += other.file_count;
+                        other
+                            .examples
+                            .into_iter()
+                            .for_each(|path| entry.add_example(path));
+                    }
+                    None => {
+                        merged.insert(value, other);
+                    }
+                }
+            }
+        }
+        for (key, group) in other.name_groups {
+            self.name_groups.entry(key).or_default().merge(group);
+        }
+        self
+    }
+}
+
+impl Summary {
+    /// Combines the summaries of disjoint scans, e.g. JSON reports written
+    /// on separate machines, without their per-file data.
+    ///
+    /// Counts, totals, the averages, min/max, the bit depth and sample rate
+    /// breakdowns, the file name groups, the rate check and verdict, the uniformity checks, and the longest files merge exactly. Others can't:
+    /// the distinct sample rate and channel layout counts become the largest
+    /// of the inputs (a lower bound), the geometric mean is weighted by file
+    /// count (exact unless a report had zero-length files), the average file
+    /// size assumes every file's size was known, and histograms
+    /// are dropped because their bucket edges differ per report.
+    pub fn merge(summaries: Vec<Summary>) -> Summary {
+        let mut merged = Summary::default();
+        let mut log_sum = 0.0;
+        let mut log_count = 0;
+        let mut bit_depths: BTreeMap<u16, DurationGroup> = BTreeMap::new();
+        let mut sample_rates: Option<BTreeMap<u32, DurationGroup>> = None;
+        let mut durations_by_file = Extremes::default();
+        let mut sizes_by_file = Extremes::default();
+        let mut modified_by_file = Extremes::default();
+        let mut uniformity: BTreeMap<FormatDimension, BTreeMap<u32, DimensionValue>> =
+            BTreeMap::new();
+        let mut name_groups: Option<BTreeMap<String, DurationGroup>> = None;
+        let mut per_channel: Option<(usize, DurationGroup)> = None;
+        let mut longest_files = summaries
+            .iter()
+            .filter_map(|summary| summary.longest_files.as_ref().map(|longest| longest.limit))
+            .max()
+            .map(TopFiles::new);
+        let mut crest_factor_sum = 0.0;
+        let mut crest_threshold = None;
+        let mut rate_verdict: Option<Option<RateVerdict>> = None;
+        let mut resample_plan: Option<Option<ResamplePlan>> = None;
+
+        for summary in summaries {
+            if summary.file_count > 0 {
+                merged.min_duration = if merged.file_count == 0 {
+                    summary.min_duration
+                } else {
+                    merged.min_duration.min(summary.min_duration)
+                };
+                merged.max_duration = merged.max_duration.max(summary.max_duration);
+            }
+            if let Some(path) = &summary.shortest_path {
+                durations_by_file.offer(summary.min_duration, path);
+            }
+            if let Some(path) = &summary.longest_path {
+                durations_by_file.offer(summary.max_duration, path);
+            }
+            if let Some(geometric_mean) = summary.geometric_mean.filter(|mean| !mean.is_zero()) {
+                log_sum += geometric_mean.as_secs_f64().ln() * summary.file_count as f64;
+                log_count += summary.file_count;
+            }
+            merged.file_count += summary.file_count;
+            merged.total_duration += summary.total_duration;
+            merged.total_channel_seconds += summary.total_channel_seconds;
+            merged.multichannel_files += summary.multichannel_files;
+            merged.total_audio_bytes += summary.total_audio_bytes;
+            merged.total_overhead_bytes += summary.total_overhead_bytes;
+            merged.total_file_bytes += summary.total_file_bytes;
+            for file in summary
+                .smallest_file
+                .into_iter()
+                .chain(summary.largest_file)
+            {
+                sizes_by_file.offer(file.bytes, &file.path);
+            }
+            for file in summary.oldest_file.into_iter().chain(summary.newest_file) {
+                modified_by_file.offer(file.modified, &file.path);
+            }
+            merged.distinct_sample_rates = merged
+                .distinct_sample_rates
+                .max(summary.distinct_sample_rates);
+            merged.distinct_channel_layouts = merged
+                .distinct_channel_layouts
+                .max(summary.distinct_channel_layouts);
+            merged.rf64_files += summary.rf64_files;
+            merged.rifx_files += summary.rifx_files;
+            merged.gzipped_files += summary.gzipped_files;
+            merged.raw_files += summary.raw_files;
+            merged.error_count += summary.error_count;
+            merged.misnamed_count += summary.misnamed_count;
+            merged.sub_frame_count += summary.sub_frame_count;
+            merged.suspicious_spec_count += summary.suspicious_spec_count;
+            merged.directories_scanned += summary.directories_scanned;
+            merged.cyclic_symlinks_pruned += summary.cyclic_symlinks_pruned;
+            merged.hardlink_duplicates += summary.hardlink_duplicates;
+            merged.recently_modified_skipped += summary.recently_modified_skipped;
+            merged.metadata_unavailable += summary.metadata_unavailable;
+            for depth in summary.bit_depths {
+                bit_depths.entry(depth.bits_per_sample).or_default().merge(
+                    DurationGroup::reported(depth.file_count, depth.total_duration),
+                );
+            }
+            if let Some(rates) = summary.sample_rates {
+                let merged_rates = sample_rates.get_or_insert_default();
+                for rate in rates {
+                    merged_rates.entry(rate.sample_rate).or_default().merge(
+                        DurationGroup::reported(rate.file_count, rate.total_duration),
+                    );
+                }
+            }
+            if let Some(groups) = summary.name_groups {
+                let merged_groups = name_groups.get_or_insert_default();
+                for group in groups {
+                    merged_groups
+                        .entry(group.key)
+                        .or_default()
+                        .merge(DurationGroup::reported(
+                            group.file_count,
+                            group.total_duration,
+                        ));
+                }
+            }
+            if let Some(plan) = &summary.resample_plan {
+                let merged_plan =
+                    resample_plan.get_or_insert_with(|| Some(ResamplePlan::new(plan.target)));
+                match merged_plan {
+                    Some(merged_plan) if merged_plan.target == plan.target => {
+                        merged_plan.merge(plan)
+                    }
+                    // Plans for different target formats don't combine.
+                    _ => *merged_plan = None,
+                }
+            }
+            if let Some(top) = &mut longest_files {
+                for file in summary
+                    .longest_files
+                    .iter()
+                    .flat_map(|longest| &longest.files)
+                {
+                    top.offer(file.duration, &file.path);
+                }
+            }
+            if let Some(stats) = summary.per_channel {
+                let (physical, channels) = per_channel.get_or_insert_default();
+                *physical += stats.physical_file_count;
+                channels.merge(DurationGroup::reported(
+                    stats.file_count,
+                    stats.total_duration,
+                ));
+            }
+            if let Some(check) = summary.float_sample_check {
+                let merged_check = merged.float_sample_check.get_or_insert_default();
+                merged_check.files_checked += check.files_checked;
+                merged_check.bad_files.extend(check.bad_files);
+            }
+            if let Some(check) = summary.crest_factor {
+                let merged_check = merged.crest_factor.get_or_insert_default();
+                merged_check.files_measured += check.files_measured;
+                crest_factor_sum += check.mean_db.unwrap_or(0.0) * check.files_measured as f64;
+                // Each report listed files below its own threshold; the merged
+                // list uses the lowest, which every kept file is below.
+                crest_threshold = Some(
+                    crest_threshold.map_or(check.threshold_db, |threshold: f64| {
+                        threshold.min(check.threshold_db)
+                    }),
+                );
+                merged_check.low_files.extend(check.low_files);
+            }
+            if let Some(check) = summary.noise_floor {
+                let merged_check = merged.noise_floor.get_or_insert_default();
+                merged_check.silent_files += check.silent_files;
+                merged_check.files.extend(check.files);
+            }
+            if let Some(sample) = summary.sample {
+                // Shards sampled separately add up; the first seed is kept.
+                let merged_sample = merged.sample.get_or_insert(SampleEstimate {
+                    seed: sample.seed,
+                    matched_files: 0,
+                    sampled_files: 0,
+                    unreadable_files: 0,
+                    estimated_total_duration: Duration::ZERO,
+                });
+                merged_sample.matched_files += sample.matched_files;
+                merged_sample.sampled_files += sample.sampled_files;
+                merged_sample.unreadable_files += sample.unreadable_files;
+                merged_sample.estimated_total_duration += sample.estimated_total_duration;
+            }
+            if let Some(check) = summary.strict_wav_check {
+                let merged_check = merged.strict_wav_check.get_or_insert_default();
+                merged_check.files_checked += check.files_checked;
+                merged_check.nonconforming.extend(check.nonconforming);
+            }
+            if let Some(check) = summary.decode_check {
+                let merged_check = merged.decode_check.get_or_insert_default();
+                merged_check.files_decoded += check.files_decoded;
+                merged_check.mismatches.extend(check.mismatches);
+            }
+            if let Some(check) = summary.byte_rate_check {
+                let merged_check = merged.byte_rate_check.get_or_insert_default();
+                merged_check.files_checked += check.files_checked;
+                merged_check.mismatches.extend(check.mismatches);
+                merged_check
+                    .block_align_mismatches
+                    .extend(check.block_align_mismatches);
+            }
+            if let Some(rates) = summary.nonstandard_rates {
+                merged
+                    .nonstandard_rates
+                    .get_or_insert_with(Vec::new)
+                    .extend(rates);
+            }
+            if let Some(verdict) = summary.rate_verdict {
+                let merged_verdict = rate_verdict.get_or_insert_with(|| {
+                    Some(RateVerdict::new(verdict.sample_rate, 0, Vec::new()))
+                });
+                match merged_verdict {
+                    Some(merged_verdict) if merged_verdict.sample_rate == verdict.sample_rate => {
+                        merged_verdict.file_count += verdict.file_count;
+                        merged_verdict.mismatched.extend(verdict.mismatched);
+                    }
+                    // Verdicts against different rates don't combine.
+                    _ => *merged_verdict = None,
+                }
+            }
+            for check in summary.uniformity {
+                let values = uniformity.entry(check.dimension).or_default();
+                for value in check.values {
+                    let entry = values.entry(value.value).or_insert_with(|| DimensionValue {
+                        value: value.value,
+                        file_count: 0,
+                        examples: Vec::new(),
+                    });
+                    entry.file_count += value.file_count;
+                    value
+                        .examples
+                        .into_iter()
+                        .for_each(|path| entry.add_example(path));
+                }
+            }
+        }
+
+        merged.error_rate = error_rate(merged.file_count, merged.error_count);
+        if merged.file_count > 0 {
+            merged.average_duration = mean_duration(merged.total_duration, merged.file_count);
+            merged.average_file_bytes = merged.total_file_bytes / merged.file_count as u64;
+        }
+        if log_count > 0 {
+            merged.geometric_mean =
+                Some(Duration::from_secs_f64((log_sum / log_count as f64).exp()));
+        }
+        // A report without paths (from an older version) may hold the true
+        // extreme, in which case no path is known for it.
+        merged.shortest_path = durations_by_file
+            .min
+            .filter(|(duration, _)| *duration == merged.min_duration)
+            .map(|(_, path)| path);
+        merged.longest_path = durations_by_file
+            .max
+            .filter(|(duration, _)| *duration == merged.max_duration)
+            .map(|(_, path)| path);
+        (merged.smallest_file, merged.largest_file) = sized_files(sizes_by_file);
+        (merged.oldest_file, merged.newest_file) = dated_files(modified_by_file);
+        merged.distinct_bit_depths = bit_depths.len();
+        merged.name_groups = name_groups.map(name_group_stats);
+        merged.per_channel =
+            per_channel.map(|(physical, channels)| PerChannelStats::new(physical, channels));
+        merged.longest_files = longest_files.map(TopFiles::into_longest_files);
+        if let Some(rates) = sample_rates {
+            merged.distinct_sample_rates = merged.distinct_sample_rates.max(rates.len());
+            merged.most_common_sample_rate = most_common_sample_rate(&rates);
+            merged.sample_rates = Some(sample_rate_stats(rates));
+        }
+        merged.bit_depths = bit_depths
+            .into_iter()
+            .map(|(bits_per_sample, group)| BitDepthStats {
+                bits_per_sample,
+                file_count: group.count,
+                total_duration: group.total(),
+                average_duration: group.average(),
+            })
+            .collect();
+        merged.float_sample_check = merged
+            .float_sample_check
+            .map(|check| FloatSampleCheck::new(check.files_checked, check.bad_files));
+        merged.crest_factor = merged.crest_factor.map(|check| {
+            let threshold = crest_threshold.unwrap_or_default();
+            let low_files = check
+                .low_files
+                .into_iter()
+                .filter(|file| file.crest_factor_db < threshold)
+                .collect();
+            CrestFactorCheck::new(check.files_measured, crest_factor_sum, threshold, low_files)
+        });
+        merged.noise_floor = merged
+            .noise_floor
+            .map(|check| NoiseFloorCheck::new(check.silent_files, check.files));
+        merged.byte_rate_check = merged.byte_rate_check.map(|check| {
+            ByteRateCheck::new(
+                check.files_checked,
+                check.mismatches,
+                check.block_align_mismatches,
+            )
+        });
+        merged.strict_wav_check = merged
+            .strict_wav_check
+            .map(|check| StrictWavCheck::new(check.files_checked, check.nonconforming));
+        merged.decode_check = merged
+            .decode_check
+            .map(|check| DecodeCheck::new(check.files_decoded, check.mismatches));
+        if let Some(rates) = &mut merged.nonstandard_rates {
+            rates.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+        merged.resample_plan = resample_plan.flatten();
+        merged.rate_verdict = rate_verdict.flatten().map(|verdict| {
+            RateVerdict::new(verdict.sample_rate, verdict.file_count, verdict.mismatched)
+        });
+        merged.uniformity = uniformity
+            .into_iter()
+            .map(|(dimension, values)| UniformityCheck {
+                dimension,
+                values: values.into_values().collect(),
+            })
+            .collect();
+        merged
+    }
+
+    /// Summarizes a whole scan, including the walk's own counters.
+    pub fn from_scan(result: &ScanResult) -> Self {
+        let summary = Summary::from_aggregate(result.aggregate.clone(), &result.errors);
+        Summary {
+            sample: result.sample.map(|stats| {
+                SampleEstimate::new(stats, summary.total_duration, summary.file_count)
+            }),
+            directories_scanned: result.directories_scanned,
+            cyclic_symlinks_pruned: result.cyclic_symlinks_pruned,
+            hardlink_duplicates: result.hardlink_duplicates,
+            recently_modified_skipped: result.recently_modified_skipped,
+            metadata_unavailable: result.metadata_unavailable,
+            float_sample_check: result.float_sample_check.clone(),
+            crest_factor: result.crest_factor.clone(),
+            noise_floor: result.noise_floor.clone(),
+            byte_rate_check: result.byte_rate_check.clone(),
+            strict_wav_check: result.strict_wav_check.clone(),
+            decode_check: result.decode_check.clone(),
+            ..summary
+        }
+    }
+
+    /// Summarizes a list of already collected files.
+    pub fn new(files: &[WavInfo], errors: &[ScanError], options: &SummaryOptions) -> Self {
+        let aggregate = files
+            .par_iter()
+            .fold(
+                || Aggregate::new(options),
+                |mut aggregate, info| {
+                    aggregate.add(info);
+                    aggregate
+                },
+            )
+            .reduce(|| Aggregate::new(options), Aggregate::merge);
+        Summary::from_aggregate(aggregate, errors)
+    }
+
+    /// Rewrites every file path in the summary with `f`, e.g. to change
+    /// separators before printing. Nothing reads the files by these paths.
+    pub fn map_paths(&mut self, f: impl Fn(&str) -> String) {
+        let f = &f;
+        let map = |path: &mut String| *path = f(path);
+        // Printed lossily either way, so mapping the lossy form loses nothing.
+        let map_path = |path: &mut PathBuf| *path = PathBuf::from(f(&path.to_string_lossy()));
+        self.shortest_path.iter_mut().for_each(map);
+        self.longest_path.iter_mut().for_each(map);
+        for file in self.smallest_file.iter_mut().chain(&mut self.largest_file) {
+            map(&mut file.path);
+        }
+        for file in self.oldest_file.iter_mut().chain(&mut self.newest_file) {
+            map(&mut file.path);
+        }
+        for rate in self.nonstandard_rates.iter_mut().flatten().chain(
+            self.rate_verdict
+                .iter_mut()
+                .flat_map(|verdict| &mut verdict.mismatched),
+        ) {
+            map(&mut rate.path);
+        }
+        for value in self
+            .uniformity
+            .iter_mut()
+            .flat_map(|check| &mut check.values)
+        {
+            value.examples.iter_mut().for_each(map);
+        }
+        for file in self
+            .float_sample_check
+            .iter_mut()
+            .flat_map(|check| &mut check.bad_files)
+        {
+            map(&mut file.path);
+        }
+        for file in self
+            .crest_factor
+            .iter_mut()
+            .flat_map(|check| &mut check.low_files)
+        {
+            map_path(&mut file.path);
+        }
+        for file in self
+            .noise_floor
+            .iter_mut()
+            .flat_map(|check| &mut check.files)
+        {
+            map_path(&mut file.path);
+        }
+        if let Some(check) = &mut self.byte_rate_check {
+            check
+                .mismatches
+                .iter_mut()
+                .for_each(|mismatch| map(&mut mismatch.path));
+            check
+                .block_align_mismatches
+                .iter_mut()
+                .for_each(|mismatch| map(&mut mismatch.path));
+        }
+        for file in self
+            .longest_files
+            .iter_mut()
+            .flat_map(|longest| &mut longest.files)
+        {
+            map(&mut file.path);
+        }
+        for file in self
+            .strict_wav_check
+            .iter_mut()
+            .flat_map(|check| &mut check.nonconforming)
+        {
+            map(&mut file.path);
+        }
+        for mismatch in self
+            .decode_check
+            .iter_mut()
+            .flat_map(|check| &mut check.mismatches)
+        {
+            map_path(&mut mismatch.path);
+        }
+    }
+
+    pub fn from_aggregate(mut aggregate: Aggregate, errors: &[ScanError]) -> Self {
+        let file_count = aggregate.file_count;
+        let total_duration = aggregate.total_duration.duration();
+        let average_duration = mean_duration(total_duration, file_count);
+        // Merge order depends on thread scheduling; sort so the output doesn't.
+        aggregate.durations.sort_unstable();
+        aggregate
+            .nonstandard_rates
+            .sort_by(|a, b| a.path.cmp(&b.path));
+        let Extremes {
+            min: shortest,
+            max: longest,
+        } = std::mem::take(&mut aggregate.durations_by_file);
+        let (smallest_file, largest_file) =
+            sized_files(std::mem::take(&mut aggregate.sizes_by_file));
+        let (oldest_file, newest_file) =
+            dated_files(std::mem::take(&mut aggregate.modified_by_file));
+
+        Summary {
+            file_count,
+            total_duration,
+            average_duration,
+            geometric_mean: (aggregate.options.geometric_mean && aggregate.nonzero_count > 0).then(
+                || {
+                    Duration::from_secs_f64(
+                        (aggregate.log_duration_sum / aggregate.nonzero_count as f64).exp(),
+                    )
+                },
+            ),
+            min_duration: shortest
+                .as_ref()
+                .map_or(Duration::ZERO, |(duration, _)| *duration),
+            max_duration: longest
+                .as_ref()
+                .map_or(Duration::ZERO, |(duration, _)| *duration),
+            shortest_path: shortest
+                .as_ref()
+                .map(|(_, path)| path.to_string_lossy().into_owned()),
+            longest_path: longest
+                .as_ref()
+                .map(|(_, path)| path.to_string_lossy().into_owned()),
+            shortest_disk_path: shortest.map(|(_, path)| PathBuf::from(path)),
+            longest_disk_path: longest.map(|(_, path)| PathBuf::from(path)),
+            total_channel_seconds: aggregate.channel_duration.duration().as_secs_f64(),
+            multichannel_files: aggregate.multichannel_files,
+            total_audio_bytes: aggregate.total_audio_bytes,
+            total_overhead_bytes: aggregate.total_overhead_bytes,
+            total_file_bytes: aggregate.total_file_bytes,
+            average_file_bytes: aggregate
+                .total_file_bytes
+                .checked_div(aggregate.sized_file_count as u64)
+                .unwrap_or(0),
+            smallest_file,
+            largest_file,
+            oldest_file,
+            newest_file,
+            distinct_sample_rates: aggregate.sample_rates.len(),
+            most_common_sample_rate: most_common_sample_rate(&aggregate.sample_rates),
+            distinct_channel_layouts: aggregate.channel_layouts.len(),
+            distinct_bit_depths: aggregate.bit_depths.len(),
+            rf64_files: aggregate.rf64_files,
+            rifx_files: aggregate.rifx_files,
+            gzipped_files: aggregate.gzipped_files,
+            raw_files: aggregate.raw_files,
+            error_count: errors.len(),
+            error_rate: error_rate(file_count, errors.len()),
+            misnamed_count: errors
+                .iter()
+                .filter(|error| matches!(error, ScanError::Misnamed { .. }))
+                .count(),
+            sub_frame_count: errors
+                .iter()
+                .filter(|error| matches!(error, ScanError::SubFrame { .. }))
+                .count(),
+            suspicious_spec_count: errors
+                .iter()
+                .filter(|error| matches!(error, ScanError::SuspiciousSpec { .. }))
+                .count(),
+            directories_scanned: 0,
+            cyclic_symlinks_pruned: 0,
+            hardlink_duplicates: 0,
+            recently_modified_skipped: 0,
+            metadata_unavailable: 0,
+            bit_depths: aggregate
+                .bit_depths
+                .into_iter()
+                .map(|(bits_per_sample, group)| BitDepthStats {
+                    bits_per_sample,
+                    file_count: group.count,
+                    total_duration: group.total(),
+                    average_duration: group.average(),
+                })
+                .collect(),
+            name_groups: aggregate
+                .options
+                .group_pattern
+                .is_some()
+                .then(|| name_group_stats(std::mem::take(&mut aggregate.name_groups))),
+            resample_plan: aggregate.resample_plan.take(),
+            longest_files: aggregate
+                .longest_files
+                .take()
+                .map(TopFiles::into_longest_files),
+            per_channel: aggregate.options.per_channel.then(|| {
+                PerChannelStats::new(
+                    file_count,
+                    DurationGroup {
+                        count: aggregate.channel_count,
+                        total: std::mem::take(&mut aggregate.channel_duration),
+                    },
+                )
+            }),
+            sample_rates: aggregate
+                .options
+                .plan_transcode
+                .then(|| sample_rate_stats(aggregate.sample_rates)),
+            median_duration: aggregate
+                .options
+                .median
+                .then(|| median(&aggregate.durations))
+                .flatten(),
+            histogram: aggregate
+                .options
+                .histogram_buckets
+                .map(|buckets| build_histogram(&aggregate.durations, buckets)),
+            nonstandard_rates: aggregate
+                .options
+                .allowed_rates
+                .is_some()
+                .then_some(aggregate.nonstandard_rates),
+            rate_verdict: aggregate.options.required_rate.map(|sample_rate| {
+                RateVerdict::new(
+                    sample_rate,
+                    file_count,
+                    std::mem::take(&mut aggregate.off_required_rate),
+                )
+            }),
+            uniformity: aggregate
+                .options
+                .require_uniform
+                .iter()
+                .map(|&dimension| UniformityCheck {
+                    dimension,
+                    values: aggregate
+                        .uniformity
+                        .remove(&dimension)
+                        .unwrap_or_default()
+                        .into_values()
+                        .collect(),
+                })
+                .collect(),
+            float_sample_check: None,
+            crest_factor: None,
+            noise_floor: None,
+            byte_rate_check: None,
+            strict_wav_check: None,
+            decode_check: None,
+            sample: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samples::CrestFactor;
+    use crate::test_support::{wav_info, wav_info_with_bits};
+    use hound::WavSpec;
+    use std::path::PathBuf;
+
+    fn summarize(files: &[WavInfo]) -> Summary {
+        Summary::new(files, &[], &SummaryOptions::default())
+    }
+
+    #[test]
+    fn test_total_channel_seconds() {
+        let files = vec![wav_info(10, 1), wav_info(10, 2), wav_info(3, 6)];
+        let summary = summarize(&files);
+        assert_eq!(summary.total_channel_seconds, 10.0 + 20.0 + 18.0);
+        assert_eq!(summary.multichannel_files, 2);
+        assert_eq!(summarize(&files[..1]).multichannel_files, 0);
+    }
+
+    #[test]
+    fn test_total_overhead_bytes_skips_unknown_sizes() {
+        let files = vec![
+            WavInfo {
+                file_size: Some(1044),
+                data_size: Some(1000),
+                ..wav_info(1, 1)
+            },
+            WavInfo {
+                file_size: Some(500),
+                data_size: None,
+                ..wav_info(1, 1)
+            },
+            // A data chunk declaring more than the file holds counts as zero.
+            WavInfo {
+                file_size: Some(100),
+                data_size: Some(4000),
+                ..wav_info(1, 1)
+            },
+        ];
+        let summary = summarize(&files);
+        assert_eq!(summary.total_overhead_bytes, 44);
+        assert_eq!(summary.total_file_bytes, 1644);
+        assert_eq!(summary.total_audio_bytes, 5000);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_duration_extremes_keep_disk_paths() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let non_utf8 = PathBuf::from(OsString::from_vec(b"caf\xe9.wav".to_vec()));
+        let files = vec![
+            WavInfo {
+                path: non_utf8.clone(),
+                ..wav_info(1, 1)
+            },
+            WavInfo {
+                path: PathBuf::from("long.wav"),
+                ..wav_info(5, 1)
+            },
+        ];
+        let summary = summarize(&files);
+        assert_eq!(summary.shortest_path.as_deref(), Some("caf\u{fffd}.wav"));
+        assert_eq!(summary.shortest_disk_path, Some(non_utf8));
+        assert_eq!(summary.longest_disk_path, Some(PathBuf::from("long.wav")));
+        // Reports don't carry them.
+        let json = serde_json::to_string(&summary).unwrap();
+        assert!(!json.contains("disk_path"));
+    }
+
+    #[test]
+    fn test_file_size_extremes() {
+        let sized = |name: &str, file_size| WavInfo {
+            path: PathBuf::from(name),
+            file_size,
+            ..wav_info(1, 1)
+        };
+        let files = vec![
+            sized("b.wav", Some(300)),
+            sized("a.wav", Some(100)),
+            sized("c.wav", Some(300)),
+            sized("d.wav", None),
+        ];
+        let summary = summarize(&files);
+        assert_eq!(summary.shortest_path.as_deref(), Some("a.wav"));
+        assert_eq!(summary.average_file_bytes, 233);
+        assert_eq!(summary.smallest_file.unwrap().path, "a.wav");
+        // Equal sizes resolve to the first path, whatever the merge order.
+        assert_eq!(summary.largest_file.unwrap().path, "b.wav");
+
+        let empty = summarize(&[]);
+        assert_eq!(empty.average_file_bytes, 0);
+        assert!(empty.smallest_file.is_none());
+    }
+
+    #[test]
+    fn test_error_rate() {
+        assert_eq!(error_rate(3, 1), Some(0.25));
+        assert_eq!(error_rate(4, 0), Some(0.0));
+        assert_eq!(error_rate(0, 2), Some(1.0));
+        assert_eq!(error_rate(0, 0), None);
+    }
+
+    #[test]
+    fn test_total_duration_is_exact() {
+        let frames = |count, rate| WavInfo {
+            duration: crate::frames_duration(count, rate),
+            spec: WavSpec {
+                sample_rate: rate,
+                ..wav_info(0, 1).spec
+            },
+            ..wav_info(0, 1)
+        };
+        let mut files = vec![frames(1, 44100); 100_000];
+        files.extend(vec![frames(7, 48000); 30_000]);
+
+        // 100000 / 44100 s plus 210000 / 48000 s. Summed per file, each
+        // single-frame file is about a quarter nanosecond long and each
+        // seven-frame one a third short, which adds up to 16 µs.
+        let per_file: Duration = files.iter().map(|info| info.duration).sum();
+        assert_eq!(per_file, Duration::from_nanos(6_642_590_000));
+        assert_eq!(
+            summarize(&files).total_duration,
+            Duration::from_nanos(6_642_573_696)
+        );
+
+        // The per-group totals are exact in the same way, so they add up to it.
+        let summary = Summary::new(
+            &files,
+            &[],
+            &SummaryOptions {
+                plan_transcode: true,
+                per_channel: true,
+                ..Default::default()
+            },
+        );
+        let rates: Duration = summary
+            .sample_rates
+            .iter()
+            .flatten()
+            .map(|rate| rate.total_duration)
+            .sum();
+        assert_eq!(rates, summary.total_duration);
+        assert_eq!(summary.bit_depths[0].total_duration, summary.total_duration);
+        assert_eq!(
+            summary.per_channel.unwrap().total_duration,
+            summary.total_duration
+        );
+    }
+
+    #[test]
+    fn test_mean_duration() {
+        assert_eq!(mean_duration(Duration::ZERO, 0), Duration::ZERO);
+        assert_eq!(
+            mean_duration(Duration::from_nanos(5), 2),
+            Duration::from_nanos(3)
+        );
+        // A count that doesn't fit a u32 isn't truncated.
+        assert_eq!(
+            mean_duration(Duration::from_secs(10), 5_000_000_000),
+            Duration::from_nanos(2)
+        );
+
+        // 200000 single frames at 44.1 kHz average 22675.74 ns, which a
+        // truncating division would report as 22675.
+        let mut aggregate = Aggregate::new(&SummaryOptions::default());
+        let frame = WavInfo {
+            duration: crate::frames_duration(1, 44100),
+            ..wav_info(0, 1)
+        };
+        for _ in 0..200_000 {
+            aggregate.add(&frame);
+        }
+        let summary = Summary::from_aggregate(aggregate, &[]);
+        assert_eq!(summary.total_duration, Duration::from_nanos(4_535_147_392));
+        assert_eq!(summary.average_duration, Duration::from_nanos(22_676));
+    }
+
+    #[test]
+    fn test_median_duration() {
+        let options = SummaryOptions {
+            median: true,
+            ..Default::default()
+        };
+        let median = |secs: &[u64]| {
+            let files: Vec<WavInfo> = secs.iter().map(|&secs| wav_info(secs, 1)).collect();
+            Summary::new(&files, &[], &options).median_duration
+        };
+        assert_eq!(median(&[9, 1, 4]), Some(Duration::from_secs(4)));
+        assert_eq!(median(&[9, 1, 4, 2]), Some(Duration::from_secs(3)));
+        assert_eq!(median(&[]), None);
+        assert!(summarize(&[wav_info(1, 1)]).median_duration.is_none());
+    }
+
+    #[test]
+    fn test_per_channel_totals() {
+        let files = [wav_info(10, 2), wav_info(4, 1), wav_info(6, 6)];
+        let options = SummaryOptions {
+            per_channel: true,
+            ..Default::default()
+        };
+        let stats = Summary::new(&files, &[], &options).per_channel.unwrap();
+        assert_eq!((stats.physical_file_count, stats.file_count), (3, 9));
+        assert_eq!(stats.total_duration, Duration::from_secs(20 + 4 + 36));
+        assert_eq!(stats.average_duration, Duration::from_nanos(6_666_666_667));
+
+        let merged = Summary::merge(vec![
+            Summary::new(&files[..1], &[], &options),
+            Summary::new(&files[1..], &[], &options),
+        ]);
+        assert_eq!(merged.per_channel.unwrap(), stats);
+        assert!(summarize(&files).per_channel.is_none());
+    }
+
+    #[test]
+    fn test_sample_estimate_leaves_out_unreadable_files() {
+        let stats = SampleStats {
+            seed: 1,
+            matched: 10,
+            sampled: 4,
+        };
+        // One sampled file failed; the three read average 2 s each.
+        let estimate = SampleEstimate::new(stats, Duration::from_secs(6), 3);
+        assert_eq!(estimate.unreadable_files, 1);
+        assert_eq!(estimate.estimated_total_duration, Duration::from_secs(20));
+
+        let none_read = SampleEstimate::new(stats, Duration::ZERO, 0);
+        assert_eq!(none_read.estimated_total_duration, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_longest_files() {
+        let files: Vec<WavInfo> = [7, 3, 9, 3, 1, 9, 5, 3]
+            .iter()
+            .enumerate()
+            .map(|(index, &secs)| WavInfo {
+                path: PathBuf::from(format!("{index}.wav")),
+                ..wav_info(secs, 1)
+            })
+            .collect();
+        // The naive ranking: sort everything, longest first, ties by path.
+        let mut ranked: Vec<RankedFile> = files
+            .iter()
+            .map(|info| RankedFile {
+                path: info.path.to_string_lossy().into_owned(),
+                duration: info.duration,
+            })
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.duration
+                .cmp(&a.duration)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+
+        for n in [1, 4, 5, 8, 20, usize::MAX] {
+            let options = SummaryOptions {
+                top: Some(n),
+                ..Default::default()
+            };
+            let expected = &ranked[..n.min(ranked.len())];
+            let top = Summary::new(&files, &[], &options).longest_files.unwrap();
+            assert_eq!((top.limit, &top.files[..]), (n, expected), "top {n}");
+
+            let (mut left, mut right) = (Aggregate::new(&options), Aggregate::new(&options));
+            files[..3].iter().for_each(|info| left.add(info));
+            files[3..].iter().for_each(|info| right.add(info));
+            let merged = Summary::from_aggregate(right.merge(left), &[]);
+            assert_eq!(
+                merged.longest_files.unwrap().files,
+                expected,
+                "merged aggregates, top {n}"
+            );
+
+            let merged = Summary::merge(vec![
+                Summary::new(&files[5..], &[], &options),
+                Summary::new(&files[..5], &[], &options),
+            ]);
+            assert_eq!(
+                merged.longest_files.unwrap().files,
+                expected,
+                "merged summaries, top {n}"
+            );
+        }
+        assert!(summarize(&files).longest_files.is_none());
+    }
+
+    #[test]
+    fn test_name_groups() -> anyhow::Result<()> {
+        let named = |name: &str, secs| WavInfo {
+            path: PathBuf::from("takes").join(name),
+            ..wav_info(secs, 1)
+        };
+        let options = SummaryOptions {
+            group_pattern: Some(Regex::new("^([a-z]+)_\\d+")?),
+            ..Default::default()
+        };
+        let first = Summary::new(
+            &[named("alice_1.wav", 10), named("bob_1.wav", 4)],
+            &[],
+            &options,
+        );
+        let second = Summary::new(
+            &[named("alice_2.wav", 20), named("notes.wav", 1)],
+            &[],
+            &options,
+        );
+        let rows = |groups: &[NameGroupStats]| -> Vec<(String, usize, u64)> {
+            groups
+                .iter()
+                .map(|group| {
+                    (
+                        group.key.clone(),
+                        group.file_count,
+                        group.total_duration.as_secs(),
+                    )
+                })
+                .collect()
+        };
+        let merged = Summary::merge(vec![first, second]);
+        assert_eq!(
+            rows(merged.name_groups.as_ref().unwrap()),
+            [
+                (UNMATCHED_GROUP.to_string(), 1, 1),
+                ("alice".to_string(), 2, 30),
+                ("bob".to_string(), 1, 4)
+            ]
+        );
+        assert_eq!(
+            merged.name_groups.unwrap()[1].average_duration,
+            Duration::from_secs(15)
+        );
+        assert!(summarize(&[named("alice_1.wav", 1)]).name_groups.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_modification_time_span() -> anyhow::Result<()> {
+        let day = |name: &str, days: Option<u64>| WavInfo {
+            path: PathBuf::from(name),
+            modified: days.map(|days| SystemTime::UNIX_EPOCH + Duration::from_secs(days * 86_400)),
+            ..wav_info(1, 1)
+        };
+        let first = summarize(&[day("a.wav", Some(19_000)), day("b.wav", None)]);
+        let second = summarize(&[day("c.wav", Some(19_010)), day("d.wav", Some(19_003))]);
+        let oldest = first.oldest_file.clone().unwrap();
+        assert_eq!(oldest.path, "a.wav");
+        assert_eq!(second.newest_file.as_ref().unwrap().path, "c.wav");
+
+        let json = serde_json::to_value(&first)?;
+        assert_eq!(json["oldest_file"]["modified"], "2022-01-08T00:00:00Z");
+        let merged = Summary::merge(vec![serde_json::from_value(json)?, second]);
+        assert_eq!(merged.oldest_file.as_ref(), Some(&oldest));
+        let newest = merged.newest_file.unwrap();
+        assert_eq!(newest.path, "c.wav");
+        assert_eq!(time_span_days(&oldest, &newest), 10.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bit_depth_breakdown() {
+        let files = vec![
+            wav_info_with_bits(2, 1, 24),
+            wav_info_with_bits(1, 1, 16),
+            wav_info_with_bits(3, 1, 16),
+            wav_info_with_bits(10, 2, 24),
+        ];
+        let breakdown = summarize(&files).bit_depths;
+
+        assert_eq!(breakdown.len(), 2);
+        assert_eq!(breakdown[0].bits_per_sample, 16);
+        assert_eq!(breakdown[0].file_count, 2);
+        assert_eq!(breakdown[0].total_duration, Duration::from_secs(4));
+        assert_eq!(breakdown[0].average_duration, Duration::from_secs(2));
+        assert_eq!(breakdown[1].bits_per_sample, 24);
+        assert_eq!(breakdown[1].average_duration, Duration::from_secs(6));
+    }
+
+    #[test]
+    fn test_sample_rate_totals_longest_first() {
+        let at_rate = |secs, sample_rate| {
+            let mut info = wav_info(secs, 1);
+            info.spec.sample_rate = sample_rate;
+            info
+        };
+        let files = vec![
+            at_rate(5, 16000),
+            at_rate(2, 48000),
+            at_rate(4, 16000),
+            at_rate(20, 44100),
+        ];
+        let options = SummaryOptions {
+            plan_transcode: true,
+            ..Default::default()
+        };
+        let rates = Summary::new(&files, &[], &options).sample_rates.unwrap();
+
+        let order: Vec<(u32, usize, u64)> = rates
+            .iter()
+            .map(|rate| {
+                (
+                    rate.sample_rate,
+                    rate.file_count,
+                    rate.total_duration.as_secs(),
+                )
+            })
+            .collect();
+        assert_eq!(order, vec![(44100, 1, 20), (16000, 2, 9), (48000, 1, 2)]);
+        assert!(summarize(&files).sample_rates.is_none());
+    }
+
+    #[test]
+    fn test_most_common_sample_rate() {
+        let at_rate = |secs, sample_rate| {
+            let mut info = wav_info(secs, 1);
+            info.spec.sample_rate = sample_rate;
+            info
+        };
+        let mode = |files: &[WavInfo]| {
+            summarize(files)
+                .most_common_sample_rate
+                .map(|rate| (rate.sample_rate, rate.file_count))
+        };
+
+        let files = vec![at_rate(1, 16000), at_rate(1, 16000), at_rate(20, 44100)];
+        assert_eq!(mode(&files), Some((16000, 2)));
+        // Equal counts: the larger total duration wins, then the higher rate.
+        let files = vec![at_rate(3, 16000), at_rate(5, 48000), at_rate(5, 44100)];
+        assert_eq!(mode(&files), Some((48000, 1)));
+        let files = vec![at_rate(5, 22050), at_rate(5, 8000)];
+        assert_eq!(mode(&files), Some((22050, 1)));
+        assert_eq!(mode(&[]), None);
+    }
+
+    #[test]
+    fn test_distinct_counts() {
+        let files = vec![
+            wav_info_with_bits(1, 1, 16),
+            wav_info_with_bits(1, 2, 16),
+            wav_info_with_bits(1, 2, 24),
+        ];
+        let summary = summarize(&files);
+        assert_eq!(summary.distinct_sample_rates, 1);
+        assert_eq!(summary.distinct_channel_layouts, 2);
+        assert_eq!(summary.distinct_bit_depths, 2);
+
+        assert_eq!(summarize(&[]).distinct_sample_rates, 0);
+    }
+
+    #[test]
+    fn test_nonstandard_rates() {
+        let at_rate = |rate| WavInfo {
+            path: PathBuf::from(format!("{rate}.wav")),
+            spec: WavSpec {
+                sample_rate: rate,
+                ..wav_info(1, 1).spec
+            },
+            ..wav_info(1, 1)
+        };
+        let files = vec![
+            at_rate(44100),
+            at_rate(11025),
+            at_rate(47999),
+            at_rate(8000),
+        ];
+
+        let check = |allowed: &[u32]| {
+            let options = SummaryOptions {
+                allowed_rates: Some(allowed.to_vec()),
+                ..Default::default()
+            };
+            Summary::new(&files, &[], &options)
+                .nonstandard_rates
+                .unwrap()
+        };
+        assert_eq!(
+            check(&[]),
+            vec![
+                NonstandardRate {
+                    path: "11025.wav".to_string(),
+                    sample_rate: 11025
+                },
+                NonstandardRate {
+                    path: "47999.wav".to_string(),
+                    sample_rate: 47999
+                },
+            ]
+        );
+        assert_eq!(check(&[11025]).len(), 1);
+
+        // The check is off unless requested.
+        assert!(summarize(&files).nonstandard_rates.is_none());
+    }
+
+    #[test]
+    fn test_rate_verdict() {
+        let at_rate = |rate| WavInfo {
+            path: PathBuf::from(format!("{rate}.wav")),
+            spec: WavSpec {
+                sample_rate: rate,
+                ..wav_info(1, 1).spec
+            },
+            ..wav_info(1, 1)
+        };
+        let files = vec![
+            at_rate(48000),
+            at_rate(44100),
+            at_rate(48000),
+            at_rate(16000),
+        ];
+        let required = |rate| SummaryOptions {
+            required_rate: Some(rate),
+            ..Default::default()
+        };
+
+        let verdict = Summary::new(&files, &[], &required(48000))
+            .rate_verdict
+            .unwrap();
+        assert!(!verdict.compatible());
+        assert_eq!(verdict.file_count, 4);
+        let mismatched: Vec<(&str, u32)> = verdict
+            .mismatched
+            .iter()
+            .map(|file| (file.path.as_str(), file.sample_rate))
+            .collect();
+        assert_eq!(mismatched, vec![("16000.wav", 16000), ("44100.wav", 44100)]);
+
+        let verdict = Summary::new(&[files[0].clone(), files[2].clone()], &[], &required(48000))
+            .rate_verdict
+            .unwrap();
+        assert!(verdict.compatible());
+
+        let merged = Summary::merge(vec![
+            Summary::new(&files[2..], &[], &required(48000)),
+            Summary::new(&files[..2], &[], &required(48000)),
+        ]);
+        assert_eq!(
+            merged.rate_verdict.unwrap(),
+            Summary::new(&files, &[], &required(48000))
+                .rate_verdict
+                .unwrap()
+        );
+
+        // Verdicts against different rates don't combine.
+        let merged = Summary::merge(vec![
+            Summary::new(&files[..2], &[], &required(48000)),
+            Summary::new(&files[2..], &[], &required(16000)),
+        ]);
+        assert!(merged.rate_verdict.is_none());
+        assert!(summarize(&files).rate_verdict.is_none());
+    }
+
+    #[test]
+    fn test_merge_resample_plans() {
+        let files: Vec<WavInfo> = (1..=4).map(|secs| wav_info(secs, 2)).collect();
+        let to = |sample_rate| SummaryOptions {
+            resample_target: Some(TargetFormat {
+                sample_rate: Some(sample_rate),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let merged = Summary::merge(vec![
+            Summary::new(&files[..1], &[], &to(16000)),
+            Summary::new(&files[1..], &[], &to(16000)),
+        ]);
+        assert_eq!(
+            merged.resample_plan,
+            Summary::new(&files, &[], &to(16000)).resample_plan
+        );
+
+        // Plans for different target formats don't combine.
+        let merged = Summary::merge(vec![
+            Summary::new(&files[..1], &[], &to(16000)),
+            Summary::new(&files[1..], &[], &to(8000)),
+        ]);
+        assert!(merged.resample_plan.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_merge_matches_sequential() {
+        let files: Vec<WavInfo> = (1..=9)
+            .map(|i| wav_info_with_bits(i, 1 + (i % 2) as u16, 16))
+            .collect();
+        let options = SummaryOptions {
+            histogram_buckets: Some(3),
+            ..Default::default()
+        };
+
+        let mut sequential = Aggregate::new(&options);
+        files.iter().for_each(|info| sequential.add(info));
+        let (mut left, mut right) = (Aggregate::new(&options), Aggregate::new(&options));
+        files[..4].iter().for_each(|info| left.add(info));
+        files[4..].iter().for_each(|info| right.add(info));
+        // An empty partial must not disturb the minimum.
+        let merged = Aggregate::new(&options).merge(right).merge(left);
+
+        let sequential = format!("{:?}", Summary::from_aggregate(sequential, &[]));
+        assert_eq!(
+            format!("{:?}", Summary::from_aggregate(merged, &[])),
+            sequential
+        );
+    }
+
+    #[test]
+    fn test_geometric_mean() {
+        let files = vec![wav_info(1, 1), wav_info(0, 1), wav_info(100, 1)];
+        let options = SummaryOptions {
+            geometric_mean: true,
+            ..Default::default()
+        };
+        let mean = Summary::new(&files, &[], &options).geometric_mean.unwrap();
+        // sqrt(1 * 100), with the zero-length file skipped.
+        assert!((mean.as_secs_f64() - 10.0).abs() < 1e-9);
+
+        assert!(summarize(&files).geometric_mean.is_none());
+        assert!(Summary::new(&[], &[], &options).geometric_mean.is_none());
+    }
+
+    #[test]
+    fn test_uniformity_checks() {
+        let at_rate = |name: &str, rate| WavInfo {
+            path: PathBuf::from(name),
+            spec: WavSpec {
+                sample_rate: rate,
+                ..wav_info(1, 2).spec
+            },
+            ..wav_info(1, 2)
+        };
+        let files: Vec<WavInfo> = ["e", "d", "c", "b", "a"]
+            .iter()
+            .map(|name| at_rate(name, 44100))
+            .chain([at_rate("z", 48000)])
+            .collect();
+        let options = SummaryOptions {
+            require_uniform: vec![FormatDimension::Rate, FormatDimension::Channels],
+            ..Default::default()
+        };
+        let summary = Summary::new(&files, &[], &options);
+
+        let [rate, channels] = &summary.uniformity[..] else {
+            panic!("expected two checks");
+        };
+        assert!(!rate.passed());
+        assert_eq!(rate.values[0].value, 44100);
+        assert_eq!(rate.values[0].file_count, 5);
+        assert_eq!(rate.values[0].examples, vec!["a", "b", "c"]);
+        assert_eq!(rate.values[1].examples, vec!["z"]);
+        assert!(channels.passed());
+        assert_eq!(channels.values[0].value, 2);
+    }
+
+    #[test]
+    fn test_merge_crest_factors() {
+        let low = |path: &str, crest_factor_db| CrestFactor {
+            path: PathBuf::from(path),
+            crest_factor_db,
+        };
+        let with_crest = |check| Summary {
+            crest_factor: Some(check),
+            ..Default::default()
+        };
+        let first = CrestFactorCheck::new(2, 4.0 + 20.0, 6.0, vec![low("a", 4.0)]);
+        let second = CrestFactorCheck::new(2, 5.0 + 7.0, 8.0, vec![low("c", 7.0), low("b", 5.0)]);
+
+        let merged = Summary::merge(vec![
+            with_crest(first),
+            Summary::default(),
+            with_crest(second),
+        ])
+        .crest_factor
+        .unwrap();
+        assert_eq!(merged.files_measured, 4);
+        assert_eq!(merged.mean_db, Some(9.0));
+        // The stricter threshold applies to the merged list.
+        assert_eq!(merged.threshold_db, 6.0);
+        assert_eq!(merged.low_files, vec![low("a", 4.0), low("b", 5.0)]);
+    }
+
+    #[test]
+    fn test_merge_round_trips_through_json() -> anyhow::Result<()> {
+        let options = SummaryOptions {
+            histogram_buckets: Some(2),
+            require_uniform: vec![FormatDimension::Bits],
+            plan_transcode: true,
+            ..Default::default()
+        };
+        let first = vec![wav_info_with_bits(4, 1, 16), wav_info_with_bits(2, 2, 24)];
+        let second = vec![wav_info_with_bits(10, 1, 16)];
+        let reparse = |files: &[WavInfo]| -> anyhow::Result<Summary> {
+            let json = serde_json::to_string(&Summary::new(files, &[], &options))?;
+            Ok(serde_json::from_str(&json)?)
+        };
+
+        let merged = Summary::merge(vec![reparse(&first)?, reparse(&[])?, reparse(&second)?]);
+        let all: Vec<WavInfo> = first.iter().chain(&second).cloned().collect();
+        let direct = Summary::new(&all, &[], &options);
+
+        assert_eq!(merged.file_count, 3);
+        assert_eq!(merged.total_duration, direct.total_duration);
+        assert_eq!(merged.average_duration, direct.average_duration);
+        assert_eq!(merged.min_duration, Duration::from_secs(2));
+        assert_eq!(merged.max_duration, Duration::from_secs(10));
+        assert_eq!(merged.total_channel_seconds, direct.total_channel_seconds);
+        assert_eq!(merged.bit_depths, direct.bit_depths);
+        assert_eq!(merged.sample_rates, direct.sample_rates);
+        assert_eq!(
+            merged.most_common_sample_rate,
+            direct.most_common_sample_rate
+        );
+        assert_eq!(merged.uniformity, direct.uniformity);
+        assert!(merged.histogram.is_none());
+
+        // Reports written before a field existed still parse.
+        let old: Summary =
+            serde_json::from_str(r#"{"file_count": 2, "total_duration_secs": 3.5}"#)?;
+        assert_eq!(old.total_duration, Duration::from_millis(3500));
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_histogram() {
+        let durations: Vec<Duration> = [0, 1, 2, 5, 9, 10].map(Duration::from_secs).to_vec();
+        let buckets = build_histogram(&durations, 5);
+
+        assert_eq!(buckets.len(), 5);
+        assert_eq!((buckets[0].range_start, buckets[0].range_end), (0.0, 2.0));
+        assert_eq!((buckets[4].range_start, buckets[4].range_end), (8.0, 10.0));
+        let counts: Vec<usize> = buckets.iter().map(|b| b.count).collect();
+        // The maximum lands in the last bucket rather than past the end.
+        assert_eq!(counts, vec![2, 1, 1, 0, 2]);
+        assert_eq!(buckets[4].total_seconds, 19.0);
+    }
+
+    #[test]
+    fn test_build_histogram_single_value() {
+        let durations = vec![Duration::from_secs(3); 4];
+        let buckets = build_histogram(&durations, 10);
+
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].count, 4);
+        assert_eq!((buckets[0].range_start, buckets[0].range_end), (3.0, 3.0));
+    }
+
+    #[test]
+    fn test_summary_json_includes_histogram() -> anyhow::Result<()> {
+        let files = vec![wav_info(1, 1), wav_info(3, 2)];
+        let summary = Summary::new(
+            &files,
+            &[],
+            &SummaryOptions {
+                histogram_buckets: Some(2),
+                ..Default::default()
+            },
+        );
+        let json: serde_json::Value = serde_json::to_value(&summary)?;
+
+        assert_eq!(json["file_count"], 2);
+        assert_eq!(json["total_duration_secs"], 4.0);
+        assert_eq!(json["histogram"][1]["range_start"], 2.0);
+        assert_eq!(json["histogram"][1]["total_seconds"], 3.0);
+
+        let without = serde_json::to_value(summarize(&files))?;
+        assert!(without.get("histogram").is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+//! Helpers for building synthetic WAV files and corpora, shared by the unit
+//! tests and the benchmarks. Not part of the stable API.
+
+use crate::{DurationSource, WavInfo};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A 16-bit integer spec with the given channel count and sample rate.
+pub fn pcm16_spec(channels: u16, sample_rate: u32) -> WavSpec {
+    WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    }
+}
+
+/// Writes a silent WAV file with `frames` sample frames.
+pub fn write_wav(path: &Path, spec: WavSpec, frames: u32) -> hound::Result<()> {
+    let mut writer = WavWriter::create(path, spec)?;
+    let mut frame_writer = writer.get_i16_writer(frames * spec.channels as u32);
+    for _ in 0..frames * spec.channels as u32 {
+        frame_writer.write_sample(0);
+    }
+    frame_writer.flush()?;
+    writer.finalize()
+}
+
+/// Generates `file_count` short 16-bit WAV files under `root`, spread over
+/// nested subdirectories with varying durations, channel counts, and sample
+/// rates. Returns the paths in creation order.
+pub fn synthetic_tree(root: &Path, file_count: usize) -> hound::Result<Vec<PathBuf>> {
+    const RATES: [u32; 3] = [8000, 16000, 44100];
+    let mut paths = Vec::with_capacity(file_count);
+    for i in 0..file_count {
+        let dir = root.join(format!("d{}", i % 8)).join(format!("s{}", i % 3));
+        fs::create_dir_all(&dir)?;
+        let spec = pcm16_spec(1 + (i % 2) as u16, RATES[i % RATES.len()]);
+        // Between 0.1 s and 1 s of audio.
+        let frames = spec.sample_rate / 10 * (1 + (i % 10) as u32);
+        let path = dir.join(format!("file_{i:05}.wav"));
+        write_wav(&path, spec, frames)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// An in-memory 44.1 kHz 16-bit result, for tests of the aggregation code.
+pub fn wav_info(secs: u64, channels: u16) -> WavInfo {
+    wav_info_with_bits(secs, channels, 16)
+}
+
+/// Like [`wav_info`], with a specific bit depth.
+pub fn wav_info_with_bits(secs: u64, channels: u16, bits_per_sample: u16) -> WavInfo {
+    WavInfo {
+        path: PathBuf::from(format!("{secs}s.wav")),
+        duration: Duration::from_secs(secs),
+        spec: WavSpec {
+            bits_per_sample,
+            ..pcm16_spec(channels, 44100)
+        },
+        source: DurationSource::DataChunk,
+        file_size: None,
+        data_size: None,
+        modified: None,
+    }
+}
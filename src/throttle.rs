@@ -0,0 +1,102 @@
+//! Paces the scan's workers so they use about a given share of the
+//! machine's CPU time, for scanning on a laptop without spinning every core.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How much CPU time may accrue unused, so a burst of quick files after a
+/// pause doesn't run unthrottled. In wall time at the limiter's rate.
+const BURST: Duration = Duration::from_millis(50);
+
+/// A token bucket shared by the worker threads. It fills with CPU time at
+/// the target share of the machine, each file's work is taken out of it,
+/// and a worker that overdraws it sleeps until it is covered again.
+///
+/// The work of a file is its wall time, which also counts waiting on the
+/// disk, so the limit is approximate: an I/O-bound scan is slowed down more
+/// than its actual CPU use requires.
+#[derive(Debug)]
+pub struct CpuLimiter {
+    /// CPU seconds that may be spent per wall-clock second.
+    rate: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// CPU seconds available; negative while workers are paying back work.
+    available: f64,
+    refilled: Instant,
+}
+
+impl CpuLimiter {
+    /// A limiter keeping the workers at `percent` of `cores` CPU cores.
+    pub fn new(percent: u8, cores: usize) -> Self {
+        CpuLimiter {
+            rate: f64::from(percent) / 100.0 * cores as f64,
+            bucket: Mutex::new(Bucket {
+                available: 0.0,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    /// A limiter for this machine, using every core the process may run on.
+    pub fn for_this_machine(percent: u8) -> Self {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        CpuLimiter::new(percent, cores)
+    }
+
+    /// Runs `work`, then sleeps as long as the bucket is overdrawn.
+    pub fn pace<T>(&self, work: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = work();
+        let pause = self.charge(start.elapsed(), Instant::now());
+        if !pause.is_zero() {
+            std::thread::sleep(pause);
+        }
+        result
+    }
+
+    /// Takes `work` out of the bucket at `now` and returns how long the
+    /// caller must sleep to pay back what it overdrew.
+    fn charge(&self, work: Duration, now: Instant) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+        bucket.available =
+            (bucket.available + elapsed * self.rate).min(BURST.as_secs_f64() * self.rate);
+        bucket.refilled = now;
+        bucket.available -= work.as_secs_f64();
+        if bucket.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.available / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charge_paces_to_the_rate() {
+        // Half of two cores: one CPU second per second.
+        let limiter = CpuLimiter::new(50, 2);
+        let start = limiter.bucket.lock().unwrap().refilled;
+        let ms = Duration::from_millis;
+        // 100 ms of work right away overdraws the bucket by 100 ms.
+        assert_eq!(limiter.charge(ms(100), start), ms(100));
+        // A second worker finishing then waits for both files' work.
+        assert_eq!(limiter.charge(ms(100), start), ms(200));
+        // Once that is paid back, work within the burst is free.
+        assert_eq!(limiter.charge(ms(30), start + ms(250)), Duration::ZERO);
+        // Idle time doesn't bank more than the burst.
+        assert_eq!(limiter.charge(ms(150), start + ms(10_000)), ms(100));
+
+        // A quarter of one core: 10 ms of work costs 40 ms of wall time.
+        let limiter = CpuLimiter::new(25, 1);
+        let start = limiter.bucket.lock().unwrap().refilled;
+        assert_eq!(limiter.charge(ms(10), start), ms(40));
+    }
+}
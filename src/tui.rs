@@ -0,0 +1,597 @@
+//! Interactive terminal UI for `--tui`: live progress while the scan runs,
+//! then a browsable view of the files read, their format distributions, and
+//! the directories they sit in.
+
+use crate::TextStyle;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Bar, BarChart, Block, Cell, Paragraph, Row, Table, TableState, Tabs};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use wav_files_stats::{ScanResult, Summary, WavInfo};
+
+/// How often the progress screen is redrawn while the scan runs.
+const PROGRESS_TICK: Duration = Duration::from_millis(100);
+
+/// Rows moved by PageUp and PageDown.
+const PAGE: usize = 20;
+
+/// Runs `scan` on a background thread behind a progress screen, then lets
+/// the user browse the result until they quit. The result is handed back so
+/// the caller can report warnings once the terminal is restored.
+///
+/// Pressing `q` during the scan sets the flag `scan` is given, so it stops
+/// reading files, and skips browsing; the flag returned with the result is
+/// then true.
+pub fn run<F>(root: &Path, style: TextStyle, scan: F) -> anyhow::Result<(Summary, ScanResult, bool)>
+where
+    F: FnOnce(&(dyn Fn(&WavInfo) + Sync), &AtomicBool) -> anyhow::Result<(Summary, ScanResult)>
+        + Send,
+{
+    let mut terminal = ratatui::init();
+    let abandoned = AtomicBool::new(false);
+    let outcome =
+        scan_with_progress(&mut terminal, root, &abandoned, scan).and_then(|(summary, result)| {
+            let abandoned = abandoned.into_inner();
+            if !abandoned {
+                let mut app = App::new(root, &summary, &result.files, style);
+                app.browse(&mut terminal)?;
+            }
+            Ok((summary, result, abandoned))
+        });
+    ratatui::restore();
+    outcome
+}
+
+fn scan_with_progress<F>(
+    terminal: &mut DefaultTerminal,
+    root: &Path,
+    abandoned: &AtomicBool,
+    scan: F,
+) -> anyhow::Result<(Summary, ScanResult)>
+where
+    F: FnOnce(&(dyn Fn(&WavInfo) + Sync), &AtomicBool) -> anyhow::Result<(Summary, ScanResult)>
+        + Send,
+{
+    let files_read = AtomicUsize::new(0);
+    let latest = Mutex::new(PathBuf::new());
+    let on_file = |info: &WavInfo| {
+        files_read.fetch_add(1, Ordering::Relaxed);
+        latest.lock().unwrap().clone_from(&info.path);
+    };
+    std::thread::scope(|scope| {
+        let handle = scope.spawn(|| scan(&on_file, abandoned));
+        while !handle.is_finished() {
+            let stopping = abandoned.load(Ordering::Relaxed);
+            terminal.draw(|frame| {
+                let text = vec![
+                    Line::from(format!("Scanning {}", root.display())),
+                    Line::from(format!(
+                        "Files read: {}",
+                        files_read.load(Ordering::Relaxed)
+                    )),
+                    Line::from(format!("Latest: {}", latest.lock().unwrap().display())).dim(),
+                    Line::from(""),
+                    Line::from(if stopping {
+                        "Stopping after the files being read…"
+                    } else {
+                        "q: abandon the scan"
+                    })
+                    .dim(),
+                ];
+                frame.render_widget(
+                    Paragraph::new(text).block(Block::bordered().title(" wav-files-stats ")),
+                    frame.area(),
+                );
+            })?;
+            if event::poll(PROGRESS_TICK)?
+                && let Event::Key(key) = event::read()?
+                && is_quit(key)
+            {
+                abandoned.store(true, Ordering::Relaxed);
+            }
+        }
+        handle.join().expect("scan thread panicked")
+    })
+}
+
+fn is_quit(key: KeyEvent) -> bool {
+    key.kind == KeyEventKind::Press
+        && (key.code == KeyCode::Char('q')
+            || (key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Files,
+    Distributions,
+    Directories,
+}
+
+const TABS: [Tab; 3] = [Tab::Files, Tab::Distributions, Tab::Directories];
+
+impl Tab {
+    fn title(self) -> &'static str {
+        match self {
+            Tab::Files => "Files",
+            Tab::Distributions => "Distributions",
+            Tab::Directories => "Directories",
+        }
+    }
+}
+
+/// What the file table is sorted by; `s` cycles through these in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Path,
+    Duration,
+    SampleRate,
+    Channels,
+    Bits,
+    Size,
+}
+
+impl SortKey {
+    fn next(self) -> Self {
+        match self {
+            SortKey::Path => SortKey::Duration,
+            SortKey::Duration => SortKey::SampleRate,
+            SortKey::SampleRate => SortKey::Channels,
+            SortKey::Channels => SortKey::Bits,
+            SortKey::Bits => SortKey::Size,
+            SortKey::Size => SortKey::Path,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortKey::Path => "path",
+            SortKey::Duration => "duration",
+            SortKey::SampleRate => "sample rate",
+            SortKey::Channels => "channels",
+            SortKey::Bits => "bit depth",
+            SortKey::Size => "size",
+        }
+    }
+}
+
+/// A directory holding files somewhere below it, with totals over all of
+/// them, for drilling down.
+#[derive(Debug, Clone, PartialEq)]
+struct DirectoryRow {
+    /// Relative to the scan root; empty for the root itself.
+    path: PathBuf,
+    file_count: usize,
+    total_duration: Duration,
+}
+
+/// Groups `files` by every directory between the root and the file, so each
+/// row counts its whole subtree. Rows are sorted by path, root first.
+fn directory_rows(root: &Path, files: &[&WavInfo]) -> Vec<DirectoryRow> {
+    let mut directories: BTreeMap<PathBuf, (usize, Duration)> = BTreeMap::new();
+    for info in files {
+        let relative = relative_path(root, &info.path);
+        for directory in relative.ancestors().skip(1) {
+            let entry = directories.entry(directory.to_path_buf()).or_default();
+            entry.0 += 1;
+            entry.1 += info.duration;
+        }
+    }
+    directories
+        .into_iter()
+        .map(|(path, (file_count, total_duration))| DirectoryRow {
+            path,
+            file_count,
+            total_duration,
+        })
+        .collect()
+}
+
+fn relative_path<'p>(root: &Path, path: &'p Path) -> &'p Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+/// Sorts files by `key`, breaking ties by path so the order is stable
+/// across re-sorts.
+fn sort_files(files: &mut [&WavInfo], key: SortKey, reverse: bool) {
+    files.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Path => std::cmp::Ordering::Equal,
+            SortKey::Duration => a.duration.cmp(&b.duration),
+            SortKey::SampleRate => a.spec.sample_rate.cmp(&b.spec.sample_rate),
+            SortKey::Channels => a.spec.channels.cmp(&b.spec.channels),
+            SortKey::Bits => a.spec.bits_per_sample.cmp(&b.spec.bits_per_sample),
+            SortKey::Size => a.file_size.cmp(&b.file_size),
+        }
+        .then_with(|| a.path.cmp(&b.path));
+        if reverse { order.reverse() } else { order }
+    });
+}
+
+/// File counts per value of one format field, ascending by value.
+fn distribution(files: &[&WavInfo], field: impl Fn(&WavInfo) -> u32) -> Vec<(u32, u64)> {
+    let mut counts: BTreeMap<u32, u64> = BTreeMap::new();
+    for info in files {
+        *counts.entry(field(info)).or_default() += 1;
+    }
+    counts.into_iter().collect()
+}
+
+struct App<'a> {
+    root: &'a Path,
+    summary: &'a Summary,
+    style: TextStyle,
+    /// Every file read, in the current sort order.
+    files: Vec<&'a WavInfo>,
+    directories: Vec<DirectoryRow>,
+    tab: Tab,
+    sort: SortKey,
+    reverse: bool,
+    /// The directory drilled into, relative to the root; only files below
+    /// it are listed.
+    directory: Option<PathBuf>,
+    file_table: TableState,
+    directory_table: TableState,
+}
+
+impl<'a> App<'a> {
+    fn new(root: &'a Path, summary: &'a Summary, files: &'a [WavInfo], style: TextStyle) -> Self {
+        let mut files: Vec<&WavInfo> = files.iter().collect();
+        sort_files(&mut files, SortKey::Path, false);
+        App {
+            root,
+            summary,
+            style,
+            directories: directory_rows(root, &files),
+            files,
+            tab: Tab::Files,
+            sort: SortKey::Path,
+            reverse: false,
+            directory: None,
+            file_table: TableState::new().with_selected(0),
+            directory_table: TableState::new().with_selected(0),
+        }
+    }
+
+    /// The files listed in the Files tab, in display order.
+    fn visible_files(&self) -> Vec<&'a WavInfo> {
+        self.files
+            .iter()
+            .copied()
+            .filter(|info| {
+                self.directory.as_ref().is_none_or(|directory| {
+                    relative_path(self.root, &info.path).starts_with(directory)
+                })
+            })
+            .collect()
+    }
+
+    fn browse(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && !self.handle_key(key)
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Applies one key press; returns false when the user quits.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let rows = match self.tab {
+            Tab::Files => self.visible_files().len(),
+            Tab::Directories => self.directories.len(),
+            Tab::Distributions => 0,
+        };
+        let table = match self.tab {
+            Tab::Directories => &mut self.directory_table,
+            _ => &mut self.file_table,
+        };
+        let selected = table.selected().unwrap_or(0);
+        let last = rows.saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => return false,
+            KeyCode::Esc if self.tab == Tab::Files && self.directory.is_some() => {
+                self.directory = None;
+                self.file_table.select(Some(0));
+            }
+            KeyCode::Esc => return false,
+            KeyCode::Tab | KeyCode::Right => self.tab = TABS[(self.tab as usize + 1) % TABS.len()],
+            KeyCode::BackTab | KeyCode::Left => {
+                self.tab = TABS[(self.tab as usize + TABS.len() - 1) % TABS.len()]
+            }
+            KeyCode::Down | KeyCode::Char('j') => table.select(Some((selected + 1).min(last))),
+            KeyCode::Up | KeyCode::Char('k') => table.select(Some(selected.saturating_sub(1))),
+            KeyCode::PageDown => table.select(Some((selected + PAGE).min(last))),
+            KeyCode::PageUp => table.select(Some(selected.saturating_sub(PAGE))),
+            KeyCode::Home | KeyCode::Char('g') => table.select(Some(0)),
+            KeyCode::End | KeyCode::Char('G') => table.select(Some(last)),
+            KeyCode::Char('s') if self.tab == Tab::Files => {
+                self.sort = self.sort.next();
+                sort_files(&mut self.files, self.sort, self.reverse);
+            }
+            KeyCode::Char('r') if self.tab == Tab::Files => {
+                self.reverse = !self.reverse;
+                sort_files(&mut self.files, self.sort, self.reverse);
+            }
+            KeyCode::Enter if self.tab == Tab::Directories => {
+                if let Some(row) = self.directories.get(selected) {
+                    self.directory = Some(row.path.clone());
+                    self.tab = Tab::Files;
+                    self.file_table.select(Some(0));
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, tabs, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let summary = self.summary;
+        frame.render_widget(
+            Line::from(format!(
+                " {} files, total {}, average {}, shortest {}, longest {}, {} errors/warnings",
+                self.style.int(summary.file_count),
+                self.style.duration(summary.total_duration),
+                self.style.duration(summary.average_duration),
+                self.style.duration(summary.min_duration),
+                self.style.duration(summary.max_duration),
+                self.style.int(summary.error_count)
+            ))
+            .bold(),
+            header,
+        );
+        frame.render_widget(
+            Tabs::new(TABS.map(Tab::title))
+                .select(self.tab as usize)
+                .highlight_style(Style::new().reversed()),
+            tabs,
+        );
+        let help = match self.tab {
+            Tab::Files if self.directory.is_some() => {
+                " ↑↓ move  s sort  r reverse  Esc all files  Tab switch  q quit"
+            }
+            Tab::Files => " ↑↓ move  s sort  r reverse  Tab switch  q quit",
+            Tab::Distributions => " Tab switch  q quit",
+            Tab::Directories => " ↑↓ move  Enter show files  Tab switch  q quit",
+        };
+        frame.render_widget(Line::from(help).dim(), footer);
+
+        match self.tab {
+            Tab::Files => self.draw_files(frame, body),
+            Tab::Distributions => self.draw_distributions(frame, body),
+            Tab::Directories => self.draw_directories(frame, body),
+        }
+    }
+
+    fn draw_files(&mut self, frame: &mut Frame, area: Rect) {
+        let files = self.visible_files();
+        let rows = files.iter().map(|info| {
+            Row::new([
+                Cell::from(relative_path(self.root, &info.path).display().to_string()),
+                Cell::from(self.style.duration(info.duration)),
+                Cell::from(format!("{} Hz", info.spec.sample_rate)),
+                Cell::from(info.spec.channels.to_string()),
+                Cell::from(format!("{}-bit", info.spec.bits_per_sample)),
+                Cell::from(
+                    info.file_size
+                        .map(|bytes| self.style.int(bytes))
+                        .unwrap_or_default(),
+                ),
+            ])
+        });
+        let scope = match &self.directory {
+            Some(directory) => format!("{}/", directory.display()),
+            None => "all".to_string(),
+        };
+        let title = format!(
+            " {} files in {}, by {}{} ",
+            self.style.int(files.len()),
+            scope,
+            self.sort.label(),
+            if self.reverse { ", descending" } else { "" }
+        );
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Min(20),
+                Constraint::Length(12),
+                Constraint::Length(10),
+                Constraint::Length(8),
+                Constraint::Length(7),
+                Constraint::Length(14),
+            ],
+        )
+        .header(Row::new(["Path", "Duration", "Rate", "Channels", "Bits", "Bytes"]).bold())
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().title(title));
+        frame.render_stateful_widget(table, area, &mut self.file_table);
+    }
+
+    fn draw_distributions(&self, frame: &mut Frame, area: Rect) {
+        let charts = [
+            (
+                "Sample rate",
+                distribution(&self.files, |info| info.spec.sample_rate),
+                " Hz",
+            ),
+            (
+                "Channels",
+                distribution(&self.files, |info| info.spec.channels as u32),
+                " ch",
+            ),
+            (
+                "Bit depth",
+                distribution(&self.files, |info| info.spec.bits_per_sample as u32),
+                "-bit",
+            ),
+        ];
+        let areas = Layout::vertical(
+            charts
+                .iter()
+                .map(|(_, values, _)| Constraint::Length(values.len() as u16 + 2)),
+        )
+        .split(area);
+        for ((title, values, unit), area) in charts.iter().zip(areas.iter()) {
+            let bars: Vec<Bar> = values
+                .iter()
+                .map(|&(value, count)| Bar::with_label(format!("{}{}", value, unit), count))
+                .collect();
+            let chart = BarChart::horizontal(bars)
+                .bar_width(1)
+                .bar_gap(0)
+                .block(Block::bordered().title(format!(" {} (files) ", title)));
+            frame.render_widget(chart, *area);
+        }
+    }
+
+    fn draw_directories(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = self.directories.iter().map(|row| {
+            let name = if row.path.as_os_str().is_empty() {
+                "./".to_string()
+            } else {
+                format!("{}/", row.path.display())
+            };
+            Row::new([
+                name,
+                self.style.int(row.file_count),
+                self.style.duration(row.total_duration),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Min(20),
+                Constraint::Length(10),
+                Constraint::Length(14),
+            ],
+        )
+        .header(Row::new(["Directory", "Files", "Total"]).bold())
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().title(format!(
+            " {} directories ",
+            self.style.int(self.directories.len())
+        )));
+        frame.render_stateful_widget(table, area, &mut self.directory_table);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+    use wav_files_stats::Rounding;
+    use wav_files_stats::summary::SummaryOptions;
+    use wav_files_stats::test_support::wav_info;
+
+    fn at(path: &str, secs: u64) -> WavInfo {
+        WavInfo {
+            path: PathBuf::from(path),
+            ..wav_info(secs, 1)
+        }
+    }
+
+    fn style() -> TextStyle {
+        TextStyle {
+            rounding: Rounding::Nearest,
+            digit_grouping: None,
+            aligned: false,
+        }
+    }
+
+    #[test]
+    fn test_directory_rows_count_subtrees() {
+        let files = [
+            at("/r/a.wav", 1),
+            at("/r/x/b.wav", 2),
+            at("/r/x/y/c.wav", 4),
+        ];
+        let files: Vec<&WavInfo> = files.iter().collect();
+        let rows: Vec<(String, usize, u64)> = directory_rows(Path::new("/r"), &files)
+            .into_iter()
+            .map(|row| {
+                (
+                    row.path.display().to_string(),
+                    row.file_count,
+                    row.total_duration.as_secs(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("".into(), 3, 7), ("x".into(), 2, 6), ("x/y".into(), 1, 4)]
+        );
+    }
+
+    #[test]
+    fn test_sort_and_drill_down() {
+        let files = vec![at("/r/a.wav", 5), at("/r/x/b.wav", 2), at("/r/x/c.wav", 9)];
+        let summary = Summary::new(&files, &[], &SummaryOptions::default());
+        let mut app = App::new(Path::new("/r"), &summary, &files, style());
+        let names = |app: &App| -> Vec<String> {
+            app.visible_files()
+                .iter()
+                .map(|info| info.path.display().to_string())
+                .collect()
+        };
+        let press = |app: &mut App, code| app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+
+        press(&mut app, KeyCode::Char('s'));
+        assert_eq!(names(&app), ["/r/x/b.wav", "/r/a.wav", "/r/x/c.wav"]);
+        press(&mut app, KeyCode::Char('r'));
+        assert_eq!(names(&app), ["/r/x/c.wav", "/r/a.wav", "/r/x/b.wav"]);
+
+        press(&mut app, KeyCode::BackTab);
+        assert_eq!(app.tab, Tab::Directories);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.tab, Tab::Files);
+        assert_eq!(names(&app), ["/r/x/c.wav", "/r/x/b.wav"]);
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(names(&app).len(), 3);
+        assert!(!press(&mut app, KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn test_draw_every_tab() -> anyhow::Result<()> {
+        let files = vec![at("/r/a.wav", 5), at("/r/x/b.wav", 2)];
+        let summary = Summary::new(&files, &[], &SummaryOptions::default());
+        let mut app = App::new(Path::new("/r"), &summary, &files, style());
+        let mut terminal = Terminal::new(TestBackend::new(100, 20))?;
+        let mut screen = |tab| -> anyhow::Result<String> {
+            app.tab = tab;
+            terminal.draw(|frame| app.draw(frame))?;
+            Ok(terminal
+                .backend()
+                .buffer()
+                .content()
+                .iter()
+                .map(|cell| cell.symbol())
+                .collect())
+        };
+        let files = screen(Tab::Files)?;
+        assert!(files.contains("2 files, total 7s"));
+        assert!(files.contains("x/b.wav"));
+        assert!(screen(Tab::Distributions)?.contains("44100 Hz"));
+        assert!(screen(Tab::Directories)?.contains("x/"));
+        Ok(())
+    }
+}
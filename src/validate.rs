@@ -0,0 +1,386 @@
+//! Consistency checks between header fields that should agree with each
+//! other. Like the duration scan, these only read the header.
+
+use crate::WavInfo;
+use crate::header::WavHeader;
+use crate::summary::{opt_secs, secs};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How far apart, relative to the sample-count duration, the two durations
+/// may be before a file is flagged. `block_align` rounding and a trailing
+/// partial frame stay well below this.
+pub const BYTE_RATE_TOLERANCE: f64 = 0.01;
+
+/// A file whose `fmt` byte rate implies a different duration than its
+/// sample count.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ByteRateMismatch {
+    /// The file path, converted lossily if it is not valid UTF-8.
+    pub path: String,
+    /// The duration reported for the file, from its sample count.
+    #[serde(with = "secs")]
+    pub sample_duration: Duration,
+    /// The `data` chunk size divided by the declared average byte rate;
+    /// `None` if the byte rate is zero.
+    #[serde(with = "opt_secs")]
+    pub byte_rate_duration: Option<Duration>,
+    pub byte_rate: u32,
+}
+
+/// A PCM or float file whose `fmt` block alignment is not the frame size
+/// implied by its channel count and bit depth. hound still decodes such
+/// files, but they usually come from a buggy encoder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockAlignMismatch {
+    /// The file path, converted lossily if it is not valid UTF-8.
+    pub path: String,
+    /// The `block_align` field of the `fmt` chunk.
+    pub block_align: u16,
+    /// `channels * ceil(bits_per_sample / 8)`.
+    pub expected_block_align: u32,
+}
+
+/// Results of the header cross-checks of `--validate` over a whole scan.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ByteRateCheck {
+    pub files_checked: usize,
+    /// Files whose durations disagree, sorted by path.
+    pub mismatches: Vec<ByteRateMismatch>,
+    /// Files with a non-standard block alignment, sorted by path. Missing
+    /// from reports written before the check existed.
+    #[serde(default)]
+    pub block_align_mismatches: Vec<BlockAlignMismatch>,
+}
+
+impl ByteRateCheck {
+    /// Builds the check result from the mismatches found in any order.
+    pub fn new(
+        files_checked: usize,
+        mut mismatches: Vec<ByteRateMismatch>,
+        mut block_align_mismatches: Vec<BlockAlignMismatch>,
+    ) -> Self {
+        mismatches.sort_by(|a, b| a.path.cmp(&b.path));
+        block_align_mismatches.sort_by(|a, b| a.path.cmp(&b.path));
+        ByteRateCheck {
+            files_checked,
+            mismatches,
+            block_align_mismatches,
+        }
+    }
+}
+
+/// A file that reads fine but isn't canonical PCM WAV, for `strict_wav`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonCanonicalWav {
+    /// The file path, converted lossily if it is not valid UTF-8.
+    pub path: String,
+    /// Every way the file departs from a plain RIFF file with a basic PCM
+    /// or IEEE float `fmt` chunk.
+    pub reasons: Vec<String>,
+}
+
+/// Results of the canonical WAV policy check over a whole scan.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StrictWavCheck {
+    pub files_checked: usize,
+    /// Nonconforming files, sorted by path.
+    pub nonconforming: Vec<NonCanonicalWav>,
+}
+
+impl StrictWavCheck {
+    /// Builds the check result from the files found in any order.
+    pub fn new(files_checked: usize, mut nonconforming: Vec<NonCanonicalWav>) -> Self {
+        nonconforming.sort_by(|a, b| a.path.cmp(&b.path));
+        StrictWavCheck {
+            files_checked,
+            nonconforming,
+        }
+    }
+}
+
+/// One column of a validation report: a check that ran on every file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Validation {
+    /// The content is a WAV file, for `verify_magic`.
+    Magic,
+    /// The file parses as WAV.
+    Readable,
+    /// It holds at least one complete frame.
+    Size,
+    /// Its channel count and bit depth are plausible.
+    Spec,
+    /// The byte rate agrees with the sample count, for `validate`.
+    ByteRate,
+    /// The block alignment matches the frame size, for `validate`.
+    BlockAlign,
+    /// It is canonical PCM WAV, for `strict_wav`.
+    StrictWav,
+    /// A float file has no NaN or infinite samples, for `check_float_samples`.
+    FloatSamples,
+}
+
+impl Validation {
+    /// The column header in the report.
+    pub fn name(self) -> &'static str {
+        match self {
+            Validation::Magic => "magic",
+            Validation::Readable => "readable",
+            Validation::Size => "size",
+            Validation::Spec => "spec",
+            Validation::ByteRate => "byte_rate",
+            Validation::BlockAlign => "block_align",
+            Validation::StrictWav => "strict_wav",
+            Validation::FloatSamples => "float_samples",
+        }
+    }
+}
+
+/// The checks one file passed and failed. A check missing from the row
+/// didn't apply, e.g. because the file couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationRow {
+    pub path: PathBuf,
+    pub results: Vec<(Validation, bool)>,
+}
+
+impl ValidationRow {
+    pub fn new(path: &Path) -> Self {
+        ValidationRow {
+            path: path.to_path_buf(),
+            results: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, check: Validation, passed: bool) {
+        self.results.push((check, passed));
+    }
+
+    /// Whether the file passed `check`; `None` if it wasn't checked.
+    pub fn passed(&self, check: Validation) -> Option<bool> {
+        self.results
+            .iter()
+            .find(|(found, _)| *found == check)
+            .map(|&(_, passed)| passed)
+    }
+}
+
+/// Every file's results for every enabled check, for a single report of
+/// which files have which problems.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// The enabled checks, in report column order.
+    pub checks: Vec<Validation>,
+    /// One row per file, sorted by path.
+    pub rows: Vec<ValidationRow>,
+}
+
+impl ValidationReport {
+    /// Builds the report from the rows found in any order.
+    pub fn new(checks: Vec<Validation>, mut rows: Vec<ValidationRow>) -> Self {
+        rows.sort_by(|a, b| a.path.cmp(&b.path));
+        ValidationReport { checks, rows }
+    }
+}
+
+/// Lists why `header` isn't canonical PCM WAV; empty if it is.
+pub fn canonical_violations(header: &WavHeader) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if header.rf64 {
+        reasons.push("RF64/BW64 container".to_string());
+    }
+    if header.big_endian {
+        reasons.push("big-endian RIFX container".to_string());
+    }
+    if header.extensible {
+        reasons.push("WAVE_FORMAT_EXTENSIBLE fmt chunk".to_string());
+    }
+    if header.is_compressed() {
+        reasons.push(format!(
+            "codec 0x{:04X} is neither PCM nor IEEE float",
+            header.format_tag
+        ));
+    }
+    reasons
+}
+
+/// Reports the file at `path` if its `header` isn't canonical PCM WAV.
+/// Returns `None` for a conforming file.
+pub fn check_strict_wav(path: &Path, header: &WavHeader) -> Option<NonCanonicalWav> {
+    let reasons = canonical_violations(header);
+    (!reasons.is_empty()).then(|| NonCanonicalWav {
+        path: path.to_string_lossy().into_owned(),
+        reasons,
+    })
+}
+
+/// What the header cross-checks found wrong with one file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeaderCheck {
+    pub byte_rate: Option<ByteRateMismatch>,
+    pub block_align: Option<BlockAlignMismatch>,
+}
+
+/// Runs every check on the `header` of the file at `path`, with `info` as
+/// its measured duration.
+pub fn check_header(path: &Path, info: &WavInfo, header: &WavHeader) -> HeaderCheck {
+    HeaderCheck {
+        byte_rate: byte_rate_mismatch(path, info, header),
+        block_align: block_align_mismatch(path, header),
+    }
+}
+
+/// Compares the duration of `info`, read from `path`, with the one implied
+/// by the header's byte rate. Returns `None` if they agree within
+/// [`BYTE_RATE_TOLERANCE`].
+fn byte_rate_mismatch(path: &Path, info: &WavInfo, header: &WavHeader) -> Option<ByteRateMismatch> {
+    let byte_rate_duration = (header.byte_rate > 0)
+        .then(|| Duration::from_secs_f64(header.data_size as f64 / header.byte_rate as f64));
+    let agrees = byte_rate_duration.is_some_and(|duration| {
+        let expected = info.duration.as_secs_f64();
+        (duration.as_secs_f64() - expected).abs() <= expected * BYTE_RATE_TOLERANCE
+    });
+    (!agrees).then(|| ByteRateMismatch {
+        path: path.to_string_lossy().into_owned(),
+        sample_duration: info.duration,
+        byte_rate_duration,
+        byte_rate: header.byte_rate,
+    })
+}
+
+/// Compressed codecs define their own block sizes, so only PCM and float
+/// files are checked.
+fn block_align_mismatch(path: &Path, header: &WavHeader) -> Option<BlockAlignMismatch> {
+    let expected_block_align =
+        u32::from(header.channels) * u32::from(header.bits_per_sample.div_ceil(8));
+    (!header.is_compressed() && u32::from(header.block_align) != expected_block_align).then(|| {
+        BlockAlignMismatch {
+            path: path.to_string_lossy().into_owned(),
+            block_align: header.block_align,
+            expected_block_align,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculate_duration;
+    use crate::header::tests::build_header;
+    use crate::header::{self, WAVE_FORMAT_PCM};
+    use crate::test_support::{pcm16_spec, write_wav};
+    use std::fs::File;
+    use std::io::BufReader;
+    use tempfile::TempDir;
+
+    /// Measures the file at `path` and runs the header checks on it.
+    fn check_file(path: &Path) -> anyhow::Result<HeaderCheck> {
+        let header = header::read_header(BufReader::new(File::open(path)?))?;
+        Ok(check_header(path, &calculate_duration(path)?, &header))
+    }
+
+    fn check_file_strict(path: &Path) -> anyhow::Result<Option<NonCanonicalWav>> {
+        let header = header::read_header(BufReader::new(File::open(path)?))?;
+        Ok(check_strict_wav(path, &header))
+    }
+
+    #[test]
+    fn test_check_byte_rate() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let good = dir.path().join("good.wav");
+        write_wav(&good, pcm16_spec(2, 8000), 8000)?;
+        assert_eq!(check_file(&good)?.byte_rate, None);
+
+        // hound rejects PCM with an inconsistent byte rate, so the check
+        // matters for files measured from their own chunks. This IMA ADPCM
+        // file holds 2 s by its fact chunk, while its byte rate of 8000
+        // makes its 8000 data bytes 1 s long.
+        let write_adpcm = |path: &Path, byte_rate: u32| {
+            let mut bytes = build_header(0x0011, 1, 8000, 4, Some(16000), 8000);
+            bytes[28..32].copy_from_slice(&byte_rate.to_le_bytes());
+            bytes.extend(std::iter::repeat_n(0u8, 8000));
+            std::fs::write(path, bytes)
+        };
+        let bad = dir.path().join("bad.wav");
+        write_adpcm(&bad, 8000)?;
+        let mismatch = check_file(&bad)?.byte_rate.unwrap();
+        assert_eq!(mismatch.sample_duration, Duration::from_secs(2));
+        assert_eq!(mismatch.byte_rate_duration, Some(Duration::from_secs(1)));
+        assert_eq!(mismatch.byte_rate, 8000);
+
+        let fixed = dir.path().join("fixed.wav");
+        write_adpcm(&fixed, 4000)?;
+        assert_eq!(check_file(&fixed)?.byte_rate, None);
+
+        let zero = dir.path().join("zero.wav");
+        write_adpcm(&zero, 0)?;
+        let mismatch = check_file(&zero)?.byte_rate.unwrap();
+        assert_eq!(mismatch.byte_rate_duration, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonical_violations() -> anyhow::Result<()> {
+        use crate::header::tests::to_rf64;
+
+        let dir = TempDir::new()?;
+        let plain = dir.path().join("plain.wav");
+        write_wav(&plain, pcm16_spec(2, 8000), 800)?;
+        assert_eq!(check_file_strict(&plain)?, None);
+
+        // hound writes an extensible fmt chunk for more than two channels.
+        let surround = dir.path().join("surround.wav");
+        write_wav(&surround, pcm16_spec(4, 8000), 800)?;
+        let found = check_file_strict(&surround)?.unwrap();
+        assert_eq!(found.reasons, ["WAVE_FORMAT_EXTENSIBLE fmt chunk"]);
+
+        let adpcm = header::read_header(&build_header(0x0011, 1, 8000, 4, Some(16000), 8000)[..])?;
+        assert_eq!(
+            canonical_violations(&adpcm),
+            ["codec 0x0011 is neither PCM nor IEEE float"]
+        );
+        let rf64 = to_rf64(
+            build_header(WAVE_FORMAT_PCM, 1, 8000, 16, None, 100),
+            100,
+            50,
+        );
+        assert_eq!(
+            canonical_violations(&header::read_header(&rf64[..])?),
+            ["RF64/BW64 container"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_block_align() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let good = dir.path().join("good.wav");
+        write_wav(&good, pcm16_spec(2, 8000), 8000)?;
+        assert_eq!(check_file(&good)?.block_align, None);
+
+        // 16-bit stereo padded to 6-byte frames, with a byte rate to match:
+        // hound reads it, but a frame should be 4 bytes.
+        let mut bytes = build_header(WAVE_FORMAT_PCM, 2, 8000, 16, None, 48000);
+        bytes[28..32].copy_from_slice(&48000u32.to_le_bytes());
+        bytes[32..34].copy_from_slice(&6u16.to_le_bytes());
+        bytes.extend(std::iter::repeat_n(0u8, 48000));
+        let padded = dir.path().join("padded.wav");
+        std::fs::write(&padded, bytes)?;
+        let check = check_file(&padded)?;
+        assert_eq!(check.byte_rate, None);
+        let mismatch = check.block_align.unwrap();
+        assert_eq!(mismatch.block_align, 6);
+        assert_eq!(mismatch.expected_block_align, 4);
+
+        // An IMA ADPCM block holds many frames, which is not a mismatch.
+        let adpcm = dir.path().join("adpcm.wav");
+        let mut bytes = build_header(0x0011, 1, 8000, 4, Some(16000), 8000);
+        bytes[28..32].copy_from_slice(&4000u32.to_le_bytes());
+        bytes[32..34].copy_from_slice(&256u16.to_le_bytes());
+        bytes.extend(std::iter::repeat_n(0u8, 8000));
+        std::fs::write(&adpcm, bytes)?;
+        assert_eq!(check_file(&adpcm)?.block_align, None);
+        Ok(())
+    }
+}
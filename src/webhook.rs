@@ -0,0 +1,156 @@
+//! Upload of the JSON summary for `--webhook`, so that scheduled scans can
+//! report to a central dashboard without writing a file first. The upload
+//! itself needs the `webhook` feature.
+
+#[cfg(feature = "webhook")]
+use std::time::Duration;
+#[cfg(feature = "webhook")]
+use wav_files_stats::Summary;
+
+/// How long the whole request may take, connecting included.
+#[cfg(feature = "webhook")]
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// POSTs `summary` as JSON to `url` with the extra `headers`. A response
+/// outside 2xx is an error naming its status and the redacted URL.
+#[cfg(feature = "webhook")]
+pub fn post_summary(
+    url: &str,
+    headers: &[(String, String)],
+    summary: &Summary,
+) -> anyhow::Result<()> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let mut request = agent.post(url).header("Content-Type", "application/json");
+    for (name, value) in headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request.send(&serde_json::to_vec(summary)?[..])?;
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("{} answered with status {}", redacted_url(url), status);
+    }
+    Ok(())
+}
+
+/// `url` with its user info and its query and fragment replaced by
+/// `<redacted>`, since webhook URLs often carry a password or token there.
+pub fn redacted_url(url: &str) -> String {
+    let (url, rest) = match url.find(['?', '#']) {
+        Some(end) => (&url[..end], &url[end..end + 1]),
+        None => (url, ""),
+    };
+    let authority_start = url.find("://").map_or(0, |scheme_end| scheme_end + 3);
+    let authority_end = url[authority_start..]
+        .find('/')
+        .map_or(url.len(), |end| authority_start + end);
+    let mut redacted = match url[authority_start..authority_end].rfind('@') {
+        Some(at) => format!(
+            "{}<redacted>{}",
+            &url[..authority_start],
+            &url[authority_start + at..]
+        ),
+        None => url.to_string(),
+    };
+    if !rest.is_empty() {
+        redacted.push_str(rest);
+        redacted.push_str("<redacted>");
+    }
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "webhook")]
+    use std::io::{BufRead, BufReader, Read, Write};
+    #[cfg(feature = "webhook")]
+    use std::net::TcpListener;
+    #[cfg(feature = "webhook")]
+    use std::thread;
+
+    #[test]
+    fn test_redacted_url() {
+        assert_eq!(
+            redacted_url("http://localhost/hook"),
+            "http://localhost/hook"
+        );
+        assert_eq!(
+            redacted_url("https://hooks.example.com/a@b#token"),
+            "https://hooks.example.com/a@b#<redacted>"
+        );
+        assert_eq!(
+            redacted_url("https://token@hooks.example.com"),
+            "https://<redacted>@hooks.example.com"
+        );
+    }
+
+    /// Answers one request with `status` and hands back what was sent.
+    #[cfg(feature = "webhook")]
+    fn serve_once(status: &'static str) -> anyhow::Result<(String, thread::JoinHandle<String>)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/report", listener.local_addr()?);
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(length) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap();
+                }
+                request.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            request.push_str(&String::from_utf8(body).unwrap());
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            request
+        });
+        Ok((url, server))
+    }
+
+    #[cfg(feature = "webhook")]
+    #[test]
+    fn test_post_summary() -> anyhow::Result<()> {
+        let summary = Summary {
+            file_count: 3,
+            ..Default::default()
+        };
+        let (url, server) = serve_once("204 No Content")?;
+        let headers = [("Authorization".to_string(), "Bearer secret".to_string())];
+        post_summary(&url, &headers, &summary)?;
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /report HTTP/1.1\r\n"));
+        assert!(
+            request
+                .to_ascii_lowercase()
+                .contains("authorization: bearer secret\r\n")
+        );
+        assert!(request.contains("\"file_count\":3"));
+
+        let (url, server) = serve_once("503 Service Unavailable")?;
+        let err = post_summary(&format!("{}?token=secret", url), &[], &summary).unwrap_err();
+        server.join().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}?<redacted> answered with status 503 Service Unavailable",
+                url
+            )
+        );
+        Ok(())
+    }
+}
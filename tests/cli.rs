@@ -0,0 +1,194 @@
+//! End-to-end tests of the command line: each one runs the built binary on a
+//! temporary directory of generated WAV files and checks what it prints and
+//! how it exits.
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+use wav_files_stats::test_support::{pcm16_spec, write_wav};
+
+/// Writes 1 s of mono, 2 s of stereo, and 90 s of mono audio at 8 kHz.
+fn mixed_durations(root: &Path) -> anyhow::Result<()> {
+    fs::create_dir(root.join("sub"))?;
+    write_wav(&root.join("one.wav"), pcm16_spec(1, 8000), 8000)?;
+    write_wav(&root.join("two.wav"), pcm16_spec(2, 8000), 2 * 8000)?;
+    write_wav(
+        &root.join("sub").join("long.wav"),
+        pcm16_spec(1, 8000),
+        90 * 8000,
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_no_wav_files() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    fs::write(dir.path().join("notes.txt"), "not audio")?;
+    cargo_bin_cmd!()
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("No WAV files found in the directory tree.\n")
+        .stderr("");
+    Ok(())
+}
+
+#[test]
+fn test_mixed_durations() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    mixed_durations(dir.path())?;
+    cargo_bin_cmd!()
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Total files processed: 3\n")
+                .and(predicate::str::contains("Directories scanned: 2\n"))
+                .and(predicate::str::contains("Total duration: 1m 33s\n"))
+                .and(predicate::str::contains("Average duration: 31s\n"))
+                .and(predicate::str::contains("Shortest file: 1s"))
+                .and(predicate::str::contains("Longest file: 1m 30s"))
+                .and(predicate::str::contains("Number of errors/warnings: 0\n")),
+        )
+        .stderr("");
+    Ok(())
+}
+
+#[test]
+fn test_unreadable_file_is_a_warning() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    mixed_durations(dir.path())?;
+    fs::write(dir.path().join("broken.wav"), b"this is not a RIFF file")?;
+    cargo_bin_cmd!()
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Total files processed: 3\n")
+                .and(predicate::str::contains("Number of errors/warnings: 1\n")),
+        )
+        .stderr(
+            predicate::str::contains("Failed to read WAV file")
+                .and(predicate::str::contains("broken.wav")),
+        );
+
+    // With --quiet the warnings block is left out.
+    cargo_bin_cmd!()
+        .arg(dir.path())
+        .arg("--quiet")
+        .assert()
+        .success()
+        .stderr("");
+    Ok(())
+}
+
+#[test]
+fn test_missing_path_fails() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    cargo_bin_cmd!()
+        .arg(dir.path().join("missing"))
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains("Provided path does not exist"));
+    Ok(())
+}
+
+/// A mono 16-bit big-endian RIFX file: it reads, but its samples can't be
+/// decoded, so checks on them warn.
+fn write_rifx(path: &Path, data_size: u32) -> std::io::Result<()> {
+    let mut bytes = b"RIFX".to_vec();
+    bytes.extend_from_slice(&(36 + data_size).to_be_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_be_bytes());
+    for field in [1u16, 1] {
+        bytes.extend_from_slice(&field.to_be_bytes());
+    }
+    bytes.extend_from_slice(&8000u32.to_be_bytes());
+    bytes.extend_from_slice(&16000u32.to_be_bytes());
+    for field in [2u16, 16] {
+        bytes.extend_from_slice(&field.to_be_bytes());
+    }
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_be_bytes());
+    bytes.resize(bytes.len() + data_size as usize, 0);
+    fs::write(path, bytes)
+}
+
+#[test]
+fn test_minimal_output_prints_nothing_else() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    mixed_durations(dir.path())?;
+    write_rifx(&dir.path().join("big_endian.wav"), 16000)?;
+    cargo_bin_cmd!()
+        .arg(dir.path())
+        .args(["--minimal-output", "--crest-factor"])
+        .assert()
+        .success()
+        .stdout("4\t94.000\n")
+        .stderr("");
+
+    // A failed file makes the exit status non-zero, still without a word.
+    fs::write(dir.path().join("broken.wav"), b"this is not a RIFF file")?;
+    cargo_bin_cmd!()
+        .arg(dir.path())
+        .args(["--minimal-output", "--crest-factor"])
+        .assert()
+        .failure()
+        .stdout("4\t94.000\n")
+        .stderr("");
+    Ok(())
+}
+
+#[test]
+fn test_paths_from_stdin() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    mixed_durations(dir.path())?;
+    let listed = format!(
+        "{}\n{}\n",
+        dir.path().join("one.wav").display(),
+        dir.path().join("sub").join("long.wav").display()
+    );
+    cargo_bin_cmd!()
+        .args(["--from-file", "-"])
+        .write_stdin(listed)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Total files processed: 2\n")
+                .and(predicate::str::contains("Total duration: 1m 31s\n")),
+        );
+    Ok(())
+}
+
+#[test]
+fn test_json_output() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    mixed_durations(dir.path())?;
+    let output = cargo_bin_cmd!()
+        .arg(dir.path())
+        .args(["--format", "json"])
+        .assert()
+        .success();
+    let summary: serde_json::Value = serde_json::from_slice(&output.get_output().stdout)?;
+    assert_eq!(summary["file_count"], 3);
+    assert_eq!(summary["total_duration_secs"], 93.0);
+    assert_eq!(summary["shortest_secs"], 1.0);
+    assert_eq!(summary["longest_secs"], 90.0);
+    assert_eq!(summary["multichannel_files"], 1);
+    assert_eq!(summary["error_count"], 0);
+
+    // The per-rate blocks only exist in the text output.
+    cargo_bin_cmd!()
+        .arg(dir.path())
+        .args(["--format", "json", "--group-by-rate"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains(
+            "--group-by-rate only applies to --format text",
+        ));
+    Ok(())
+}